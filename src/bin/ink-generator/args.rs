@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+///Options which take a value, as listed in the usage
+const OPTIONS: [&str; 11] = ["templates", "standard", "ext", "out", "name", "symbol", "uri", "decimals", "license", "files", "set"];
+
+///Options which do not take a value
+const FLAGS: [&str; 4] = ["external-crate", "warn-conflicts", "json", "help"];
+
+fn check_option(name: &str) -> Result<(), CliError> {
+    match OPTIONS.contains(&name) || FLAGS.contains(&name) {
+        true => Ok(()),
+        false => Err(CliError::new(&format!("Unknown option: --{}, see ink-generator help", name))),
+    }
+}
+
+#[derive(Debug)]
+pub struct CliError(String);
+
+impl CliError {
+    pub fn new(message: &str) -> CliError {
+        CliError(message.to_string())
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for CliError {}
+
+///Command line split into the subcommand and its `--name value` (or `--name=value`) options.
+/// Options can be repeated, list options additionally accept comma separated values
+#[derive(Debug, Default)]
+pub struct Args {
+    pub command: Option<String>,
+    options: HashMap<String, Vec<String>>,
+}
+
+impl Args {
+    pub fn parse<I: Iterator<Item=String>>(mut args: I) -> Result<Args, CliError> {
+        let mut parsed = Args { command: args.next(), options: HashMap::new() };
+        if let Some(name) = parsed.command.as_deref().and_then(|command| command.strip_prefix("--")) {
+            check_option(name)?;
+            parsed.options.entry(name.to_owned()).or_default();
+            parsed.command = None;
+        }
+        while let Some(arg) = args.next() {
+            let option = arg.strip_prefix("--").ok_or_else(|| CliError::new(&format!("Unexpected argument: {}", arg)))?;
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+                None if FLAGS.contains(&option) => (option.to_owned(), None),
+                None => (option.to_owned(), Some(args.next().ok_or_else(|| CliError::new(&format!("Missing value of --{}", option)))?)),
+            };
+            check_option(&name)?;
+            parsed.options.entry(name).or_default().extend(value);
+        }
+        Ok(parsed)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.get(name).and_then(|values| values.last()).map(String::as_str)
+    }
+
+    pub fn required(&self, name: &str) -> Result<&str, CliError> {
+        self.value(name).ok_or_else(|| CliError::new(&format!("Missing required option --{}", name)))
+    }
+
+    pub fn values(&self, name: &str) -> Vec<String> {
+        self.options.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn list(&self, name: &str) -> Vec<String> {
+        self.values(name).iter()
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+}
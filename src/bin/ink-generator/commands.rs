@@ -0,0 +1,229 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use futures::executor::block_on;
+use serde_json::Value;
+
+use ink_generator::{ConflictPolicy, Metadata, OutputFile};
+use ink_generator::code_loader::loader::FsLoader;
+use ink_generator::generator::dependencies::{InclusionReason, ResolvedExtension};
+use ink_generator::generator::merge::conflicts::MergeConflict;
+use ink_generator::generator::registry::load_registry;
+use ink_generator::generator::source_parser::{describe_extensions, run};
+use ink_generator::project::{generate as generate_project, GeneratedProject, GenerationRequest};
+
+use crate::args::{Args, CliError};
+
+///Templates are read from the `contracts` directory unless `--templates` is given
+pub const DEFAULT_TEMPLATES: &str = "contracts";
+
+fn templates(args: &Args) -> &str {
+    args.value("templates").unwrap_or(DEFAULT_TEMPLATES)
+}
+
+fn metadata(args: &Args) -> Result<Option<Metadata>, CliError> {
+    let decimals = args.value("decimals")
+        .map(|decimals| u8::from_str(decimals).map_err(|_| CliError::new(&format!("Invalid --decimals: {}", decimals))))
+        .transpose()?;
+    let (name, symbol, uri) = (args.value("name"), args.value("symbol"), args.value("uri"));
+    if name.is_none() && symbol.is_none() && uri.is_none() && decimals.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(Metadata::new(name.map(str::to_owned), symbol.map(str::to_owned), uri.map(str::to_owned), decimals)))
+}
+
+///`--set field=value`, value is parsed as JSON and taken as a string when it is not valid JSON
+fn constructor_value(assignment: &str) -> Result<(String, Value), CliError> {
+    let (field, value) = assignment.split_once('=').ok_or_else(|| CliError::new(&format!("Invalid --set {}, expected field=value", assignment)))?;
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()));
+    Ok((field.trim().to_owned(), value))
+}
+
+fn request(args: &Args) -> Result<GenerationRequest, CliError> {
+    let mut request = GenerationRequest::new(args.required("standard")?, args.list("ext"), templates(args));
+    request.metadata = metadata(args)?;
+    request.license_name = args.value("license").unwrap_or_default().to_owned();
+    request.use_external_crate = args.flag("external-crate");
+    if args.flag("warn-conflicts") {
+        request.conflict_policy = ConflictPolicy::Warn;
+    }
+    let files = args.list("files");
+    if !files.is_empty() {
+        request.files = files.iter()
+            .map(|file| OutputFile::from_str(file).map_err(|_| CliError::new(&format!("Unknown file: {}", file))))
+            .collect::<Result<Vec<_>, CliError>>()?;
+    }
+    request.constructor_values = args.values("set").iter()
+        .map(|assignment| constructor_value(assignment))
+        .collect::<Result<_, CliError>>()?;
+    Ok(request)
+}
+
+fn print_extensions(extensions: &[ResolvedExtension]) {
+    for extension in extensions {
+        match &extension.reason {
+            InclusionReason::Requested => println!("  {}", extension.kind.path()),
+            InclusionReason::RequiredBy(kind) => println!("  {} (required by {})", extension.kind.path(), kind.path()),
+        }
+    }
+}
+
+fn print_conflicts(conflicts: &[MergeConflict]) {
+    for conflict in conflicts {
+        eprintln!("warning: {}", conflict);
+    }
+}
+
+fn generate_checked(args: &Args) -> Result<GeneratedProject, Box<dyn Error>> {
+    let project = block_on(generate_project(request(args)?))?;
+    print_conflicts(&project.conflicts);
+    Ok(project)
+}
+
+///Generates the project and writes its files to `--out`
+pub fn generate(args: &Args) -> Result<(), Box<dyn Error>> {
+    let out = Path::new(args.required("out")?);
+    let project = generate_checked(args)?;
+
+    fs::create_dir_all(out)?;
+    for file in &project.files {
+        fs::write(out.join(&file.name), &file.content)?;
+        println!("Written: {}", out.join(&file.name).display());
+    }
+    println!("Extensions:");
+    print_extensions(&project.extensions);
+    Ok(())
+}
+
+pub fn list_standards(args: &Args) -> Result<(), Box<dyn Error>> {
+    let registry = block_on(load_registry(&FsLoader::new(templates(args))))?;
+    if args.flag("json") {
+        let names = registry.standards().iter().map(|standard| standard.name.as_str()).collect::<Vec<_>>();
+        println!("{}", serde_json::to_string(&names)?);
+        return Ok(());
+    }
+    for standard in registry.standards() {
+        let extensions = standard.extensions.iter().map(|extension| extension.path()).collect::<Vec<_>>();
+        println!("{}: {}", standard.name, extensions.join(", "));
+    }
+    Ok(())
+}
+
+pub fn list_extensions(args: &Args) -> Result<(), Box<dyn Error>> {
+    let extensions = block_on(describe_extensions(&FsLoader::new(templates(args)), args.required("standard")?))?;
+    if args.flag("json") {
+        println!("{}", serde_json::to_string_pretty(&extensions)?);
+        return Ok(());
+    }
+    for extension in extensions {
+        match &extension.description {
+            Some(description) => println!("{} - {}: {}", extension.name, extension.title, description),
+            None => println!("{} - {}", extension.name, extension.title),
+        }
+        if !extension.dependencies.is_empty() {
+            println!("  requires: {}", extension.dependencies.join(", "));
+        }
+        if !extension.conflicts.is_empty() {
+            println!("  conflicts with: {}", extension.conflicts.join(", "));
+        }
+    }
+    Ok(())
+}
+
+///Checks that the selection can be merged without writing anything
+pub fn validate(args: &Args) -> Result<(), Box<dyn Error>> {
+    let request = request(args)?;
+    let output = block_on(run(&request, request.loader().as_ref()))?;
+    print_conflicts(&output.conflicts);
+    println!("Valid {} contract with extensions:", output.standard.name);
+    print_extensions(&output.extensions);
+    Ok(())
+}
+
+enum Line<'a> {
+    Same,
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+///Line diff based on the longest common subsequence
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = match old[i] == new[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+
+    let (mut i, mut j, mut lines) = (0, 0, Vec::new());
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(Line::Same);
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+fn print_diff(name: &str, old: &str, new: &str) {
+    println!("--- {}", name);
+    println!("+++ {} (generated)", name);
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let mut line_number = 0;
+    let mut in_change = false;
+    for line in diff_lines(&old, &new) {
+        match line {
+            Line::Same => {
+                line_number += 1;
+                in_change = false;
+            }
+            Line::Removed(text) => {
+                line_number += 1;
+                if !in_change {
+                    println!("@@ line {} @@", line_number);
+                }
+                in_change = true;
+                println!("-{}", text);
+            }
+            Line::Added(text) => {
+                if !in_change {
+                    println!("@@ line {} @@", line_number + 1);
+                }
+                in_change = true;
+                println!("+{}", text);
+            }
+        }
+    }
+}
+
+///Compares the generated project with the one in `--out`. Fails when they differ
+pub fn diff(args: &Args) -> Result<(), Box<dyn Error>> {
+    let out = Path::new(args.required("out")?);
+    let project = generate_checked(args)?;
+
+    let mut changed = Vec::new();
+    for file in &project.files {
+        let existing = fs::read_to_string(out.join(&file.name)).unwrap_or_default();
+        if existing != file.content {
+            print_diff(&file.name, &existing, &file.content);
+            changed.push(file.name.clone());
+        }
+    }
+    match changed.is_empty() {
+        true => Ok(()),
+        false => Err(Box::new(CliError::new(&format!("{} differs from the generated project: {}", out.display(), changed.join(", "))))),
+    }
+}
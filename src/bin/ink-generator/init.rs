@@ -0,0 +1,121 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use futures::executor::block_on;
+use log::{debug, info};
+
+use ink_generator::{ConflictPolicy, OutputFile, prettifier, Standard};
+use ink_generator::code_loader::loader::{FsLoader, SourceLoader};
+use ink_generator::generator::{BASE_CONTRACT_FILE_TYPE, CONTRACT_EXTENSION_FILE_TYPE};
+use ink_generator::generator::dependencies::resolve_extensions;
+use ink_generator::generator::merge::{AstMerger, ContractFiles, Merger};
+use ink_generator::generator::merge::constructor_values::ConstructorValues;
+use ink_generator::generator::registry::StandardDescriptor;
+use ink_generator::generator::source_parser::{ExtensionContext, ExtensionKind};
+
+///Templates are loaded the same way as in the generator, `path` is relative to the `contracts` directory
+fn get_ast(loader: &FsLoader, path: &str) -> syn::File {
+    let contract = block_on(loader.load(path)).expect("File not found");
+    syn::parse_str::<syn::File>(&contract).expect("Failed to parse")
+}
+
+fn copy_static(extension: &str, static_content: &str, standard: Standard) {
+    write_to_file(
+        format!("contracts/{standard}/extensions/tests/{extension}/src/{static_content}.rs"),
+        &fs::read_to_string(format!("contracts/{standard}/{static_content}.rs"))
+            .expect("File not found"),
+    ).expect("Could not write to file.");
+}
+
+fn generate(destination: &str, base_files: &ContractFiles, extensions: Vec<ExtensionContext>, standard: Standard) {
+    let descriptor = StandardDescriptor::builtin(standard);
+    info!("Generating tests for: {}", destination);
+    let merged = Merger::merge(base_files, extensions, &descriptor, &None, &ConstructorValues::new(), false, ConflictPolicy::Fail).expect("Merge failed");
+
+    for (file, ast) in &merged.files {
+        let path = format!("contracts/{standard}/extensions/tests/{destination}/src/{file}");
+        write_to_file(path, &prettifier::unparse(ast)).expect("Could not write to file.");
+    }
+
+    if standard == Standard::PSP34 || standard == Standard::PSP37 {
+        copy_static(destination, "test_utils", standard);
+        copy_static(destination, "unit_tests", standard);
+    }
+}
+
+fn write_to_file<P: AsRef<Path>>(path: P, content: &str) -> io::Result<()> {
+    let path = path.as_ref();
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = File::create(path)?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// Generates test case for every extension.
+/// Dependencies of the tested extension (for example: burnable extension requires ownable extension as well)
+/// are resolved the same way as in the generator.
+fn generate_test_cases(standard: Standard, extensions: Vec<ExtensionKind>) {
+    let loader = FsLoader::new("contracts");
+    let main_path = format!("{standard}/lib{}", BASE_CONTRACT_FILE_TYPE);
+    debug!("Loading base contract from: {}", main_path);
+    let base_files = vec![OutputFile::Data, OutputFile::Traits, OutputFile::Errors].into_iter()
+        .map(|file| (file, get_ast(&loader, &format!("{standard}/{file}"))))
+        .chain(vec![(OutputFile::Main, get_ast(&loader, main_path.as_str()))])
+        .collect::<ContractFiles>();
+
+    let descriptor = StandardDescriptor::builtin(standard);
+    for kind in extensions {
+        let resolved = resolve_extensions(&descriptor, std::slice::from_ref(&kind)).expect("Invalid extension selection");
+        let dependencies = resolved.into_iter().map(|extension| {
+            let ast_path = format!("{standard}/extensions/{}{}", descriptor.extension_path(&extension.kind), CONTRACT_EXTENSION_FILE_TYPE);
+            debug!("Loading extension from: {}", ast_path);
+            ExtensionContext {
+                kind: extension.kind,
+                ast: get_ast(&loader, ast_path.as_str()),
+            }
+        }).collect();
+        generate(kind.to_string().as_str(), &base_files, dependencies, standard);
+    }
+}
+
+
+fn generate_psp22_test_cases() {
+    generate_test_cases(Standard::PSP22, vec![
+        ExtensionKind::Burnable,
+        ExtensionKind::Mintable,
+        ExtensionKind::Pausable,
+        ExtensionKind::Capped,
+        ExtensionKind::Wrapper,
+    ]);
+}
+
+fn generate_psp34_test_cases() {
+    generate_test_cases(Standard::PSP34, vec![
+        ExtensionKind::Burnable,
+        ExtensionKind::Mintable,
+        ExtensionKind::Metadata,
+        ExtensionKind::Enumerable,
+    ]);
+}
+
+fn generate_psp37_test_cases() {
+    generate_test_cases(Standard::PSP37, vec![
+        ExtensionKind::Burnable,
+        ExtensionKind::Mintable,
+        ExtensionKind::Batch,
+        ExtensionKind::Metadata,
+        ExtensionKind::Enumerable,
+    ]);
+}
+
+///Regenerates test case of every extension under `contracts/{standard}/extensions/tests`
+pub fn generate_all_test_cases() {
+    generate_psp22_test_cases();
+    generate_psp34_test_cases();
+    generate_psp37_test_cases();
+}
@@ -0,0 +1,66 @@
+use std::process;
+
+use args::{Args, CliError};
+
+mod args;
+mod commands;
+mod init;
+
+const USAGE: &str = "Generates ink! smart contracts from the Smart Beaver templates
+
+Usage: ink-generator <command> [options]
+
+Commands:
+  generate         Generates the project and writes it to --out
+  list-standards   Lists standards of the templates and their extensions
+  list-extensions  Describes extensions of --standard
+  validate         Checks that the selected extensions can be merged
+  diff             Compares the generated project with the one in --out
+  init             Regenerates extension test cases under contracts/{standard}/extensions/tests
+
+Options:
+  --templates <dir>       Directory with the templates [default: contracts]
+  --standard <name>       Standard of the contract, e.g. PSP22
+  --ext <names>           Comma separated extensions, e.g. mintable,capped
+  --out <dir>             Directory of the generated project
+  --name <name>           Token name, the package name in Cargo.toml is kept from the templates
+  --symbol <symbol>       Token symbol
+  --uri <uri>             Token URI
+  --decimals <decimals>   Token decimals
+  --license <license>     License written to Cargo.toml
+  --files <files>         Comma separated files to generate, e.g. lib.rs,Cargo.toml
+  --set <field=value>     Constructor value of an extension field, value is parsed as JSON
+  --external-crate        Uses the external crate of the standard, only lib.rs and Cargo.toml are generated
+  --warn-conflicts        Reports conflicts between extensions as warnings instead of failing
+  --json                  Prints lists as JSON";
+
+fn main() {
+    let result = Args::parse(std::env::args().skip(1)).map_err(|e| e.into()).and_then(|args| {
+        if args.flag("help") {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        match args.command.as_deref() {
+            Some("generate") => commands::generate(&args),
+            Some("list-standards") => commands::list_standards(&args),
+            Some("list-extensions") => commands::list_extensions(&args),
+            Some("validate") => commands::validate(&args),
+            Some("diff") => commands::diff(&args),
+            Some("init") => {
+                simple_logger::init().unwrap();
+                init::generate_all_test_cases();
+                Ok(())
+            }
+            Some("help") | None => {
+                println!("{}", USAGE);
+                Ok(())
+            }
+            Some(command) => Err(CliError::new(&format!("Unknown command: {}, see ink-generator help", command)).into()),
+        }
+    });
+
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
@@ -1,25 +1,104 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use futures::future::{FutureExt, LocalBoxFuture};
 use serde::{Deserialize, Serialize};
-use web_sys::Storage;
+use wasm_bindgen::JsCast;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Response, Storage};
 
 use crate::logger::console_log;
 use crate::logger::log;
 
 const CACHE_KEY_PREFIX: &str = "sb_wasm_cache_";
 
+///Name of the IndexedDB database and of the Cache API cache
+const CACHE_NAME: &str = "sb_wasm_cache";
+
 //TODO use some normal timestamp type
 pub type Timestamp = f64;
 
 pub const EXPIRATION_TIME_MILISECONDS: f64 = 10.0 * 60.0 * 1000.0;//10 min
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CacheWrapper {
-    file_content: String,
+    pub file_content: String,
     expiration_date: Option<Timestamp>,
+    ///`ETag` of the downloaded file, sent back as `If-None-Match` to revalidate the cached copy
+    #[serde(default)]
+    pub etag: Option<String>,
+    ///`Last-Modified` of the downloaded file, sent back as `If-Modified-Since` to revalidate the cached copy
+    #[serde(default)]
+    pub last_modified: Option<String>,
+}
+
+impl CacheWrapper {
+    pub fn is_expired(&self) -> bool {
+        self.expiration_date.is_some_and(|time| time <= now())
+    }
+
+    ///Cached copy can be revalidated with a conditional request instead of being downloaded again
+    pub fn has_validators(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+}
+
+///Storage of the downloaded templates
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheBackend {
+    ///`localStorage`, limited to about 5MB
+    LocalStorage,
+    IndexedDb,
+    ///`window.caches`
+    CacheApi,
+    ///Lives as long as the wasm module, shared by all generations
+    Memory,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub backend: CacheBackend,
+    ///Time in milliseconds after which the cached file is downloaded again. Files never expire when not set
+    pub ttl: Option<f64>,
+    ///Version of the template set, part of the cache key so files cached for other versions are not used
+    pub version: Option<String>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            enabled: true,
+            backend: CacheBackend::LocalStorage,
+            ttl: Some(EXPIRATION_TIME_MILISECONDS),
+            version: None,
+        }
+    }
+}
+
+fn now() -> Timestamp {
+    if cfg!(target_arch = "wasm32") {
+        js_sys::Date::now()
+    } else {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as Timestamp)
+            .unwrap_or_default()
+    }
+}
+
+trait CacheStore {
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Option<String>>;
+    fn set<'a>(&'a self, key: &'a str, value: String) -> LocalBoxFuture<'a, bool>;
+    fn clear(&self) -> LocalBoxFuture<'_, ()>;
+}
+
+struct LocalStorageStore;
+
 fn storage_ref() -> Option<Storage> {
-    //Window object should always be available
-    match web_sys::window().unwrap().local_storage() {
+    match web_sys::window()?.local_storage() {
         Ok(local_storage_opt) => local_storage_opt,
         Err(_) => {
             console_log!("Failed to get local storage");
@@ -28,55 +107,233 @@ fn storage_ref() -> Option<Storage> {
     }
 }
 
-pub fn cache_file(filepath: &str, content: &str, expiration_date: Option<Timestamp>) {
-    if let Some(storage) = storage_ref() {
-        let cache_result = serde_json::to_string(&CacheWrapper {
-            file_content: content.to_string(),
-            expiration_date,
-        }).map(|serialized_value| {
-            storage.set_item(format!("{}{}", CACHE_KEY_PREFIX, filepath).as_str(), serialized_value.as_str())
-        });
-
-        match cache_result {
-            Ok(Ok(content)) => {
-                content
-            }
-            _ => {
-                console_log!("Failed to cache file: {}", filepath)
+impl CacheStore for LocalStorageStore {
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Option<String>> {
+        async move {
+            storage_ref()?.get_item(key).unwrap_or_else(|e| {
+                console_log!("Failed to load: {} from cache [Js error: {:?}]", key, e);
+                None
+            })
+        }.boxed_local()
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: String) -> LocalBoxFuture<'a, bool> {
+        async move {
+            storage_ref().map(|storage| storage.set_item(key, &value).is_ok()).unwrap_or_default()
+        }.boxed_local()
+    }
+
+    fn clear(&self) -> LocalBoxFuture<'_, ()> {
+        async move {
+            if let Some(storage) = storage_ref() {
+                let keys = (0..storage.length().unwrap_or_default())
+                    .filter_map(|index| storage.key(index).ok().flatten())
+                    .filter(|key| key.starts_with(CACHE_KEY_PREFIX))
+                    .collect::<Vec<_>>();
+                for key in keys {
+                    let _ = storage.remove_item(&key);
+                }
             }
-        }
+        }.boxed_local()
     }
 }
 
+#[wasm_bindgen(inline_js = "
+function request(name, mode, action) {
+    return new Promise((resolve, reject) => {
+        const open = indexedDB.open(name, 1);
+        open.onupgradeneeded = () => open.result.createObjectStore('files');
+        open.onerror = () => reject(open.error);
+        open.onsuccess = () => {
+            const result = action(open.result.transaction('files', mode).objectStore('files'));
+            result.onsuccess = () => resolve(result.result);
+            result.onerror = () => reject(result.error);
+        };
+    });
+}
+export function idb_get(name, key) { return request(name, 'readonly', store => store.get(key)); }
+export function idb_set(name, key, value) { return request(name, 'readwrite', store => store.put(value, key)); }
+export function idb_clear(name) { return request(name, 'readwrite', store => store.clear()); }
+")]
+extern "C" {
+    #[wasm_bindgen(catch)]
+    async fn idb_get(name: &str, key: &str) -> Result<wasm_bindgen::JsValue, wasm_bindgen::JsValue>;
 
-pub fn try_load_from_cache(filepath: &str) -> Option<String> {
-    if let Some(storage) = storage_ref() {
-        return storage.get_item(format!("{}{}", CACHE_KEY_PREFIX, filepath).as_str()).unwrap_or_else(|e| {
-            console_log!("Failed to load: {} from cache [Js error: {:?}]", filepath, e);
-            None
-        }).map(|c| {
-            //deserialize
-            serde_json::from_str::<CacheWrapper>(c.as_str()).ok()
-        }).filter(|c| {
-            //check expiration date
-            match c {
-                None => {
-                    console_log!("Failed to deserialize: {}", filepath);
-                    false
-                }
-                Some(c) => {
-                    match c.expiration_date {
-                        None => true,
-                        Some(time) => {
-                            time > js_sys::Date::now()
-                        }
-                    }
-                }
+    #[wasm_bindgen(catch)]
+    async fn idb_set(name: &str, key: &str, value: &str) -> Result<wasm_bindgen::JsValue, wasm_bindgen::JsValue>;
+
+    #[wasm_bindgen(catch)]
+    async fn idb_clear(name: &str) -> Result<wasm_bindgen::JsValue, wasm_bindgen::JsValue>;
+}
+
+struct IndexedDbStore;
+
+impl CacheStore for IndexedDbStore {
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Option<String>> {
+        async move {
+            idb_get(CACHE_NAME, key).await.ok()?.as_string()
+        }.boxed_local()
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: String) -> LocalBoxFuture<'a, bool> {
+        async move {
+            idb_set(CACHE_NAME, key, &value).await.is_ok()
+        }.boxed_local()
+    }
+
+    fn clear(&self) -> LocalBoxFuture<'_, ()> {
+        async move {
+            let _ = idb_clear(CACHE_NAME).await;
+        }.boxed_local()
+    }
+}
+
+struct CacheApiStore;
+
+async fn open_cache() -> Option<web_sys::Cache> {
+    let caches = web_sys::window()?.caches().ok()?;
+    JsFuture::from(caches.open(CACHE_NAME)).await.ok()?.dyn_into().ok()
+}
+
+impl CacheStore for CacheApiStore {
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Option<String>> {
+        async move {
+            let response: Response = JsFuture::from(open_cache().await?.match_with_str(key)).await.ok()?.dyn_into().ok()?;
+            JsFuture::from(response.text().ok()?).await.ok()?.as_string()
+        }.boxed_local()
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: String) -> LocalBoxFuture<'a, bool> {
+        async move {
+            match (open_cache().await, Response::new_with_opt_str(Some(&value))) {
+                (Some(cache), Ok(response)) => JsFuture::from(cache.put_with_str(key, &response)).await.is_ok(),
+                _ => false,
+            }
+        }.boxed_local()
+    }
+
+    fn clear(&self) -> LocalBoxFuture<'_, ()> {
+        async move {
+            if let Some(caches) = web_sys::window().and_then(|window| window.caches().ok()) {
+                let _ = JsFuture::from(caches.delete(CACHE_NAME)).await;
             }
-        }).map(|c| {
-            //get content
-            c.unwrap().file_content
-        });
+        }.boxed_local()
     }
-    None
-}
\ No newline at end of file
+}
+
+thread_local! {
+    static MEMORY_CACHE: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+}
+
+struct MemoryStore;
+
+impl CacheStore for MemoryStore {
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Option<String>> {
+        async move {
+            MEMORY_CACHE.with(|cache| cache.borrow().get(key).cloned())
+        }.boxed_local()
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: String) -> LocalBoxFuture<'a, bool> {
+        async move {
+            MEMORY_CACHE.with(|cache| cache.borrow_mut().insert(key.to_owned(), value));
+            true
+        }.boxed_local()
+    }
+
+    fn clear(&self) -> LocalBoxFuture<'_, ()> {
+        async move {
+            MEMORY_CACHE.with(|cache| cache.borrow_mut().clear());
+        }.boxed_local()
+    }
+}
+
+fn store(backend: CacheBackend) -> Box<dyn CacheStore> {
+    match backend {
+        CacheBackend::LocalStorage => Box::new(LocalStorageStore),
+        CacheBackend::IndexedDb => Box::new(IndexedDbStore),
+        CacheBackend::CacheApi => Box::new(CacheApiStore),
+        CacheBackend::Memory => Box::new(MemoryStore),
+    }
+}
+
+///Downloaded files cached in the configured backend
+///
+/// # Examples
+///
+/// ```
+/// use futures::executor::block_on;
+/// use ink_generator::code_loader::cache_proxy::{Cache, CacheBackend, CacheConfig};
+///
+/// let config = CacheConfig { backend: CacheBackend::Memory, version: Some("1.0.0".to_owned()), ..CacheConfig::default() };
+/// block_on(Cache::new(config.clone()).store("https://templates/PSP22/lib.rs", "mod token {}"));
+/// assert_eq!(block_on(Cache::new(config.clone()).load("https://templates/PSP22/lib.rs")).unwrap(), "mod token {}");
+///
+/// let next_version = CacheConfig { version: Some("1.1.0".to_owned()), ..config.clone() };
+/// assert!(block_on(Cache::new(next_version).load("https://templates/PSP22/lib.rs")).is_none());
+/// let disabled = CacheConfig { enabled: false, ..config };
+/// assert!(block_on(Cache::new(disabled).load("https://templates/PSP22/lib.rs")).is_none());
+/// ```
+pub struct Cache {
+    config: CacheConfig,
+    store: Box<dyn CacheStore>,
+}
+
+impl Cache {
+    pub fn new(config: CacheConfig) -> Cache {
+        Cache { store: store(config.backend), config }
+    }
+
+    fn key(&self, filepath: &str) -> String {
+        match &self.config.version {
+            Some(version) => format!("{}{}/{}", CACHE_KEY_PREFIX, version, filepath),
+            None => format!("{}{}", CACHE_KEY_PREFIX, filepath),
+        }
+    }
+
+    pub async fn store(&self, filepath: &str, content: &str) {
+        self.store_with_validators(filepath, content, None, None).await
+    }
+
+    ///Stores the file with validators of the response, expiration date is set anew
+    pub async fn store_with_validators(&self, filepath: &str, content: &str, etag: Option<String>, last_modified: Option<String>) {
+        if !self.config.enabled {
+            return;
+        }
+        let serialized_value = serde_json::to_string(&CacheWrapper {
+            file_content: content.to_string(),
+            expiration_date: self.config.ttl.map(|ttl| now() + ttl),
+            etag,
+            last_modified,
+        }).expect("Cache wrapper is always serializable");
+
+        if !self.store.set(&self.key(filepath), serialized_value).await {
+            console_log!("Failed to cache file: {}", filepath)
+        }
+    }
+
+    ///Cached content of the file, unless it expired
+    pub async fn load(&self, filepath: &str) -> Option<String> {
+        self.lookup(filepath).await
+            .filter(|wrapper| !wrapper.is_expired())
+            .map(|wrapper| wrapper.file_content)
+    }
+
+    ///Cached file, including the expired one which still can be revalidated
+    pub async fn lookup(&self, filepath: &str) -> Option<CacheWrapper> {
+        if !self.config.enabled {
+            return None;
+        }
+        let cached = self.store.get(&self.key(filepath)).await?;
+        serde_json::from_str::<CacheWrapper>(&cached).map_err(|_| {
+            console_log!("Failed to deserialize: {}", filepath);
+        }).ok()
+    }
+}
+
+///Removes cached files from every backend
+pub async fn clear_cache() {
+    for backend in [CacheBackend::LocalStorage, CacheBackend::IndexedDb, CacheBackend::CacheApi, CacheBackend::Memory] {
+        store(backend).clear().await;
+    }
+}
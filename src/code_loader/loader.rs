@@ -1,11 +1,14 @@
+use std::collections::HashMap;
 use std::error::Error;
-use std::ops::Add;
+use std::path::PathBuf;
 
+use futures::future::{FutureExt, LocalBoxFuture};
 use wasm_bindgen::JsCast;
+use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{Request, RequestInit, RequestMode, Response};
+use web_sys::{Headers, Request, RequestInit, RequestMode, Response};
 
-use crate::code_loader::cache_proxy::{cache_file, EXPIRATION_TIME_MILISECONDS, try_load_from_cache};
+use crate::code_loader::cache_proxy::{Cache, CacheConfig};
 use crate::logger::console_log;
 use crate::logger::log;
 
@@ -26,27 +29,312 @@ impl std::fmt::Display for DownloadError {
 
 impl Error for DownloadError {}
 
-pub async fn load_source(filepath: &str) -> Result<String, impl Error> {
-    match try_load_from_cache(filepath) {
-        None => {
-            let mut opts = RequestInit::new();
-            opts.method("GET");
-            opts.mode(RequestMode::Cors);
-            let window = web_sys::window().unwrap();
-            let request = Request::new_with_str_and_init(filepath, &opts).unwrap();
-            let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.unwrap();
-            let resp: Response = resp_value.dyn_into().unwrap();
-            if resp.status() >= 300 {
-                return Err(DownloadError::new(filepath, resp.status()));
+///Response of the GET request, limited to what the loader uses
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+///Sends GET requests for the loader
+pub trait HttpClient {
+    fn get<'a>(&'a self, url: &'a str, headers: &'a [(&'a str, String)]) -> LocalBoxFuture<'a, Result<HttpResponse, DownloadError>>;
+}
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = fetch)]
+    fn global_fetch(input: &Request) -> js_sys::Promise;
+}
+
+///Global `fetch`, available both in the browser and in Node.js
+#[derive(Debug, Clone, Default)]
+pub struct FetchClient;
+
+impl HttpClient for FetchClient {
+    fn get<'a>(&'a self, url: &'a str, headers: &'a [(&'a str, String)]) -> LocalBoxFuture<'a, Result<HttpResponse, DownloadError>> {
+        async move {
+            let opts = RequestInit::new();
+            opts.set_method("GET");
+            opts.set_mode(RequestMode::Cors);
+            let request_headers = Headers::new().map_err(|_| DownloadError::new(url, 0))?;
+            for (name, value) in headers {
+                request_headers.set(name, value).map_err(|_| DownloadError::new(url, 0))?;
             }
-            let response_content = JsFuture::from(resp.text().unwrap()).await.unwrap().as_string().unwrap();
+            opts.set_headers(&request_headers);
+            let request = Request::new_with_str_and_init(url, &opts).map_err(|_| DownloadError::new(url, 0))?;
+            let response: Response = JsFuture::from(global_fetch(&request)).await
+                .map_err(|_| DownloadError::new(url, 0))?
+                .unchecked_into();
+            let status = response.status();
+            let body = match response.text() {
+                Ok(text) => JsFuture::from(text).await.ok().and_then(|body| body.as_string()).unwrap_or_default(),
+                Err(_) => String::new(),
+            };
+            Ok(HttpResponse {
+                status,
+                body,
+                etag: response.headers().get("ETag").ok().flatten(),
+                last_modified: response.headers().get("Last-Modified").ok().flatten(),
+            })
+        }.boxed_local()
+    }
+}
+
+///Serves the cached copy while it is fresh. Copy with validators is revalidated with a conditional request,
+/// so updated templates are used immediately while unchanged ones are not downloaded again (`304 Not Modified`).
+/// Cached copy is also served when the server is unreachable or fails with `5xx`.
+///
+/// Served cross-origin, the templates server has to expose the validators and allow the conditional headers:
+/// `Access-Control-Expose-Headers: ETag, Last-Modified` on responses and
+/// `Access-Control-Allow-Headers: If-None-Match, If-Modified-Since` on the preflight.
+/// Otherwise validators are not visible to `fetch` and cached copies are downloaded again once they expire
+pub(crate) async fn load_source(filepath: &str, cache: &Cache, client: &dyn HttpClient) -> Result<String, DownloadError> {
+    let cached = cache.lookup(filepath).await;
+    if let Some(cached) = cached.as_ref().filter(|cached| !cached.is_expired() && !cached.has_validators()) {
+        console_log!("Loaded from cache: {}", filepath);
+        return Ok(cached.file_content.clone());
+    }
+
+    let mut headers = Vec::new();
+    if let Some(etag) = cached.as_ref().and_then(|cached| cached.etag.clone()) {
+        headers.push(("If-None-Match", etag));
+    }
+    if let Some(last_modified) = cached.as_ref().and_then(|cached| cached.last_modified.clone()) {
+        headers.push(("If-Modified-Since", last_modified));
+    }
+
+    let response = match (client.get(filepath, &headers).await, cached.as_ref()) {
+        (Ok(response), Some(cached)) if response.status >= 500 => {
+            console_log!("Failed to revalidate: {}, status code: {}, using cached copy", filepath, response.status);
+            return Ok(cached.file_content.clone());
+        }
+        (Ok(response), _) => response,
+        (Err(_), Some(cached)) => {
+            console_log!("Failed to revalidate: {}, using cached copy", filepath);
+            return Ok(cached.file_content.clone());
+        }
+        (Err(error), None) => return Err(error),
+    };
+
+    match (response.status, cached) {
+        (304, Some(cached)) => {
+            console_log!("Revalidated: {}", filepath);
+            let etag = response.etag.or(cached.etag);
+            let last_modified = response.last_modified.or(cached.last_modified);
+            cache.store_with_validators(filepath, &cached.file_content, etag, last_modified).await;
+            Ok(cached.file_content)
+        }
+        (status, _) if status >= 300 => Err(DownloadError::new(filepath, status)),
+        _ => {
             console_log!("Downloaded: {}", filepath);
-            cache_file(filepath, &response_content, Some(js_sys::Date::now().add(EXPIRATION_TIME_MILISECONDS)));
-            Ok(response_content)
+            cache.store_with_validators(filepath, &response.body, response.etag, response.last_modified).await;
+            Ok(response.body)
         }
-        Some(cached_content) => {
-            console_log!("Loaded from cache: {}", filepath);
-            Ok(cached_content)
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceNotFoundError(String);
+
+impl SourceNotFoundError {
+    pub fn new(message: &str) -> SourceNotFoundError {
+        SourceNotFoundError(message.to_string())
+    }
+}
+
+impl std::fmt::Display for SourceNotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Source file not found: {}", self.0)
+    }
+}
+
+impl Error for SourceNotFoundError {}
+
+#[derive(Debug)]
+pub struct UnsupportedSourceError(String);
+
+impl UnsupportedSourceError {
+    pub fn new(message: &str) -> UnsupportedSourceError {
+        UnsupportedSourceError(message.to_string())
+    }
+}
+
+impl std::fmt::Display for UnsupportedSourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Unsupported source: {}", self.0)
+    }
+}
+
+impl Error for UnsupportedSourceError {}
+
+fn is_remote(root: &str) -> bool {
+    root.starts_with("http://") || root.starts_with("https://")
+}
+
+///Whether the file does not exist at the source, as opposed to the source being unreachable or failing
+pub fn is_not_found(error: &(dyn Error + 'static)) -> bool {
+    error.downcast_ref::<SourceNotFoundError>().is_some()
+        || error.downcast_ref::<DownloadError>().is_some_and(|download_error| download_error.1 == 404)
+}
+
+///Provides templates (registry manifest, base contract, extension fragments and static files) to the generator.
+/// `path` is relative to the templates root, e.g. `PSP22/extensions/mintable.trs`
+pub trait SourceLoader {
+    fn load<'a>(&'a self, path: &'a str) -> LocalBoxFuture<'a, Result<String, Box<dyn Error>>>;
+}
+
+///Downloads templates with `fetch`, caching them as configured
+pub struct FetchLoader {
+    url: String,
+    cache: Cache,
+    client: Box<dyn HttpClient>,
+}
+
+impl FetchLoader {
+    pub fn new(url: &str) -> FetchLoader {
+        FetchLoader::with_cache(url, CacheConfig::default())
+    }
+
+    pub fn with_cache(url: &str, cache: CacheConfig) -> FetchLoader {
+        FetchLoader::with_client(url, cache, Box::new(FetchClient))
+    }
+
+    pub fn with_client(url: &str, cache: CacheConfig, client: Box<dyn HttpClient>) -> FetchLoader {
+        FetchLoader { url: url.trim_end_matches('/').to_owned(), cache: Cache::new(cache), client }
+    }
+}
+
+impl SourceLoader for FetchLoader {
+    fn load<'a>(&'a self, path: &'a str) -> LocalBoxFuture<'a, Result<String, Box<dyn Error>>> {
+        async move {
+            Ok(load_source(&format!("{}/{}", self.url, path), &self.cache, self.client.as_ref()).await?)
+        }.boxed_local()
+    }
+}
+
+#[wasm_bindgen(inline_js = "
+export async function node_read_file(path) {
+    const module = 'node:fs/promises';
+    const fs = await import(module);
+    return fs.readFile(path, 'utf8');
+}
+")]
+extern "C" {
+    #[wasm_bindgen(catch)]
+    async fn node_read_file(path: &str) -> Result<wasm_bindgen::JsValue, wasm_bindgen::JsValue>;
+}
+
+///Loads templates in Node.js: roots starting with `http://` or `https://` are downloaded with the global `fetch`
+/// and cached as configured, other roots are read from the disk with `fs`.
+/// Backends relying on browser APIs are not available in Node.js, so only `Memory` backend keeps the files
+pub struct NodeLoader {
+    root: String,
+    cache: Cache,
+}
+
+impl NodeLoader {
+    pub fn new(root: &str) -> NodeLoader {
+        NodeLoader::with_cache(root, CacheConfig::default())
+    }
+
+    pub fn with_cache(root: &str, cache: CacheConfig) -> NodeLoader {
+        NodeLoader { root: root.trim_end_matches('/').to_owned(), cache: Cache::new(cache) }
+    }
+
+}
+
+fn js_error_property(error: &wasm_bindgen::JsValue, name: &str) -> Option<String> {
+    js_sys::Reflect::get(error, &name.into()).ok().and_then(|value| value.as_string())
+}
+
+impl SourceLoader for NodeLoader {
+    fn load<'a>(&'a self, path: &'a str) -> LocalBoxFuture<'a, Result<String, Box<dyn Error>>> {
+        async move {
+            let filepath = format!("{}/{}", self.root, path);
+            if !is_remote(&self.root) {
+                return match node_read_file(&filepath).await {
+                    Ok(content) => content.as_string().ok_or_else(|| format!("{} is not a text file", filepath).into()),
+                    Err(error) if js_error_property(&error, "code").as_deref() == Some("ENOENT") => Err(Box::new(SourceNotFoundError::new(&filepath)) as Box<dyn Error>),
+                    Err(error) => Err(format!("Failed to read {}: {}", filepath, js_error_property(&error, "message").unwrap_or_else(|| format!("{:?}", error))).into()),
+                };
+            }
+            Ok(load_source(&filepath, &self.cache, &FetchClient).await?)
+        }.boxed_local()
+    }
+}
+
+///Reads templates from the local filesystem. Remote sources are rejected, they can be downloaded only in wasm
+#[derive(Debug, Clone)]
+pub struct FsLoader {
+    root: PathBuf,
+}
+
+impl FsLoader {
+    pub fn new<P: Into<PathBuf>>(root: P) -> FsLoader {
+        FsLoader { root: root.into() }
+    }
+}
+
+impl SourceLoader for FsLoader {
+    fn load<'a>(&'a self, path: &'a str) -> LocalBoxFuture<'a, Result<String, Box<dyn Error>>> {
+        async move {
+            if is_remote(&self.root.to_string_lossy()) {
+                return Err(Box::new(UnsupportedSourceError::new(&format!("{} can not be downloaded natively, pass a directory with templates instead", self.root.display()))) as Box<dyn Error>);
+            }
+            let filepath = self.root.join(path);
+            std::fs::read_to_string(&filepath).map_err(|error| match error.kind() {
+                std::io::ErrorKind::NotFound => Box::new(SourceNotFoundError::new(&filepath.display().to_string())) as Box<dyn Error>,
+                kind => Box::new(std::io::Error::new(kind, format!("{}: {}", filepath.display(), error))),
+            })
+        }.boxed_local()
+    }
+}
+
+///Templates passed directly, keyed by path relative to the templates root. Nothing is downloaded
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use futures::executor::block_on;
+/// use ink_generator::code_loader::loader::{MemoryLoader, SourceLoader};
+///
+/// let loader = MemoryLoader::new(HashMap::from([("PSP22/lib.rs".to_owned(), "mod token {}".to_owned())]));
+/// assert_eq!(block_on(loader.load("PSP22/lib.rs")).unwrap(), "mod token {}");
+/// assert!(block_on(loader.load("PSP22/data.rs")).is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryLoader {
+    files: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new(files: HashMap<String, String>) -> MemoryLoader {
+        MemoryLoader { files }
+    }
+}
+
+impl SourceLoader for MemoryLoader {
+    fn load<'a>(&'a self, path: &'a str) -> LocalBoxFuture<'a, Result<String, Box<dyn Error>>> {
+        async move {
+            self.files.get(path)
+                .cloned()
+                .ok_or_else(|| Box::new(SourceNotFoundError::new(path)) as Box<dyn Error>)
+        }.boxed_local()
+    }
+}
+
+///Loader matching the environment: `fetch` in the browser, `fs` or `fetch` in Node.js and the filesystem natively
+pub fn default_loader(source: &str, cache: &CacheConfig) -> Box<dyn SourceLoader> {
+    if cfg!(target_arch = "wasm32") {
+        match web_sys::window() {
+            Some(_) => Box::new(FetchLoader::with_cache(source, cache.clone())),
+            None => Box::new(NodeLoader::with_cache(source, cache.clone())),
         }
+    } else {
+        Box::new(FsLoader::new(source))
     }
 }
@@ -1,4 +1,4 @@
-pub(crate) mod loader;
+pub mod loader;
 pub(crate) mod static_files;
-mod cache_proxy;
+pub mod cache_proxy;
 
@@ -1,12 +1,15 @@
+use std::collections::HashMap;
 use std::error::Error;
 
-use crate::{get_all_files, MergedFile, OutputFile, Standard};
-use crate::code_loader::loader::load_source;
+use crate::{MergedFile, OutputFile};
+use crate::code_loader::loader::SourceLoader;
 use crate::generator::manifest_parser::update_cargo_config;
+use crate::generator::merge::error_variants::{ErrorVariants, extend_errors_source};
+use crate::generator::registry::StandardDescriptor;
 use crate::prettifier::doc_comments_remove;
 
-async fn download_static_content(source: &str, standard: &str, file_string: &str) -> String {
-    match load_source(&format!("{source}/{standard}/{file_string}")).await {
+async fn download_static_content(loader: &dyn SourceLoader, standard: &str, file_string: &str) -> String {
+    match loader.load(&format!("{standard}/{file_string}")).await {
         Ok(content) => content,
         Err(_e) => "".to_owned(),
     }
@@ -29,30 +32,37 @@ impl std::fmt::Display for StaticFileDownloadError {
 
 impl Error for StaticFileDownloadError {}
 
+///Completes merged files with the static content of the standard.
+/// Files changed by extensions are taken from `merged`, the rest is downloaded as it is.
+/// Files the standard does not declare are skipped
 pub async fn with_static_content(
-    main: String,
+    merged: &HashMap<OutputFile, String>,
     license_name: &str,
-    source: &str,
-    standard: Standard,
+    loader: &dyn SourceLoader,
+    standard: &StandardDescriptor,
     files_to_process: Vec<OutputFile>,
-) -> Result<Vec<MergedFile>, impl Error> {
+    error_variants: &[ErrorVariants],
+) -> Result<Vec<MergedFile>, Box<dyn Error>> {
     let mut downloaded_files = Vec::new();
     let files_to_process_not_empty = if files_to_process.is_empty() {
-        get_all_files()
+        standard.files.clone()
     } else {
         files_to_process
     };
-    for file in files_to_process_not_empty {
+    for file in files_to_process_not_empty.into_iter().filter(|file| standard.files.contains(file)) {
         let file_name = file.to_string();
-        let content = if file == OutputFile::Main {
-            main.clone()
+        let content = if let Some(merged_content) = merged.get(&file) {
+            merged_content.clone()
         } else {
-            match download_static_content(source, standard.to_string().as_ref(), &file_name).await {
+            match download_static_content(loader, &standard.name, &file_name).await {
                 content if !content.is_empty() && file == OutputFile::Cargo => {
-                    update_cargo_config(content, Some(license_name.to_owned()), standard.get_external_crate_name())
+                    update_cargo_config(content, Some(license_name.to_owned()).filter(|license| !license.is_empty()), standard.external_crate.clone())
+                }
+                content if !content.is_empty() && file == OutputFile::Errors => {
+                    doc_comments_remove(&extend_errors_source(&content, error_variants)?)
                 }
                 content if !content.is_empty() => doc_comments_remove(&content),
-                _ => return Err(StaticFileDownloadError::new(&format!("Static content {} could not be downloaded.", file_name)))
+                _ => return Err(Box::new(StaticFileDownloadError::new(&format!("Static content {} could not be downloaded.", file_name))))
             }
         };
         downloaded_files.push(MergedFile { name: file_name, content });
@@ -1,7 +1,10 @@
 use std::borrow::Borrow;
 use std::ops::Deref;
 
-use syn::{Attribute, Block, Expr, FieldValue, FnArg, Ident, ImplItemFn, Item, ItemImpl, ItemMod, ItemStruct, parse_quote, Token, Type};
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use syn::{Attribute, Block, Expr, FieldValue, Ident, ImplItemFn, Item, ItemImpl, ItemMod, ItemStruct, parse_quote, Stmt, Token, Type};
+use syn::__private::ToTokens;
+use syn::punctuated::Punctuated;
 
 use crate::logger::console_log;
 use crate::logger::log;
@@ -14,7 +17,7 @@ pub fn generate_field_value(ident: &Ident, expr: Option<Expr>) -> FieldValue {
     FieldValue {
         attrs: vec![],
         member: parse_quote!(#ident),
-        colon_token: expr.is_some().then(|| Some(Token![:](span))).unwrap_or(None),
+        colon_token: if expr.is_some() { Some(Token![:](span)) } else { None },
         expr: match expr {
             None => parse_quote!(0),
             Some(e) => e
@@ -26,28 +29,61 @@ pub fn extract_attribute_expression(attrs: &[Attribute], searched_attribute: &At
     attrs.iter().find_map(|attr| {
         //Path comparison works only on "smart_beaver::init" part
         if compare_attributes(attr, searched_attribute) {
-            return match attr.parse_args::<Expr>() {
-                Ok(expr) => Some(expr),
-                Err(_) => None
-            };
+            return attr.parse_args::<Expr>().ok();
         }
         None
     })
 }
 
-pub fn field_to_fn_arg(field: &syn::Field) -> Option<syn::FnArg> {
-    field.ident.as_ref().map(|ident| FnArg::Typed(syn::PatType {
-        attrs: vec![],
-        colon_token: Default::default(),
-        pat: Box::new(syn::Pat::Ident(syn::PatIdent {
-            attrs: Vec::new(),
-            by_ref: None,
-            mutability: None,
-            ident: ident.clone(),
-            subpat: None,
-        })),
-        ty: Box::new(field.ty.clone()),
-    }))
+///Removes generator specific `#[smart_beaver::*]` attributes, so they do not leak into the generated code
+pub fn strip_smart_beaver_attributes(attrs: &[Attribute]) -> Vec<Attribute> {
+    attrs.iter()
+        .filter(|attr| attr.path().segments.first().map(|segment| segment.ident != "smart_beaver").unwrap_or(true))
+        .cloned()
+        .collect()
+}
+
+///Parses comma separated attribute arguments, e.g. `#[smart_beaver::guard(mutable_messages, except = [pause])]`
+pub fn parse_attribute_args(attr: &Attribute) -> Vec<Expr> {
+    attr.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+        .map(|args| args.into_iter().collect())
+        .unwrap_or_default()
+}
+
+///Returns names listed in `[a, b]` or `["a", "b"]` expression
+pub fn parse_expr_as_name_list(expr: &Expr) -> Option<Vec<String>> {
+    match expr {
+        Expr::Array(array) => array.elems.iter().map(|elem| match elem {
+            Expr::Path(path) => path.path.get_ident().map(|ident| ident.to_string()),
+            Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit_str), .. }) => Some(lit_str.value()),
+            _ => None
+        }).collect(),
+        _ => None
+    }
+}
+
+fn has_ink_flag(impl_fn: &ImplItemFn, flag: &str) -> bool {
+    let ink_attribute: Attribute = parse_quote!(#[ink]);
+    impl_fn.attrs.iter()
+        .filter(|attr| attr.path().eq(ink_attribute.path()))
+        .any(|attr| parse_attribute_args(attr).iter().any(|arg| parse_expr_as_flag(arg).as_deref() == Some(flag)))
+}
+
+///Checks if function is tagged with `#[ink(message)]`, including attributes with more arguments like `#[ink(message, payable)]`
+pub fn is_ink_message(impl_fn: &ImplItemFn) -> bool {
+    has_ink_flag(impl_fn, "message")
+}
+
+///Checks if function is tagged with `#[ink(constructor)]`, including attributes like `#[ink(constructor, payable)]`
+pub fn is_ink_constructor(impl_fn: &ImplItemFn) -> bool {
+    has_ink_flag(impl_fn, "constructor")
+}
+
+///Checks if function takes `&mut self`
+pub fn has_mutable_receiver(impl_fn: &ImplItemFn) -> bool {
+    impl_fn.sig.receiver()
+        .map(|receiver| receiver.reference.is_some() && receiver.mutability.is_some())
+        .unwrap_or(false)
 }
 
 /// Has to pass in mutable references otherwise rust borrow checker will not allow it
@@ -60,7 +96,7 @@ pub fn find_struct_by_attr(root_mod: &mut ItemMod, attr: Attribute) -> Option<&m
         Some((_brace, items)) => {
             items.iter_mut().find_map(|i| {
                 if let Item::Struct(struct_value) = i {
-                    if struct_value.attrs.contains(&attr) {
+                    if find_attribute(&struct_value.attrs, &attr).is_some() {
                         return Some(struct_value);
                     }
                 }
@@ -79,19 +115,6 @@ pub fn extract_fn_implementations(impl_block: &mut ItemImpl) -> Vec<&mut ImplIte
     }).collect()
 }
 
-/// Search for first implementation block with given attribute
-/// @TODO add param to ignore args expression
-pub fn extract_fn_implementation_by_attr(impl_block: &mut ItemImpl, searched_attribute: Attribute) -> Option<&mut ImplItemFn> {
-    impl_block.items.iter_mut().find_map(|i| {
-        if let syn::ImplItem::Fn(method) = i {
-            if method.attrs.iter().any(|a| searched_attribute.eq(a)) {
-                return Some(method);
-            }
-        }
-        None
-    })
-}
-
 /// Check if compare_target is contained in input_fragment
 /// compare_target can be only a Path part or entire attribute with nested arguments
 fn compare_attributes(input_fragment: &Attribute, compare_target: &Attribute) -> bool {
@@ -108,13 +131,13 @@ fn compare_attributes(input_fragment: &Attribute, compare_target: &Attribute) ->
 }
 
 pub fn find_attribute<'a>(attributes: &'a [Attribute], searched_attribute: &Attribute) -> Option<&'a Attribute> {
-    return attributes.iter().find(|attr| {
+    attributes.iter().find(|attr| {
         compare_attributes(attr, searched_attribute)
-    });
+    })
 }
 
 pub fn extract_impl_blocks(root_mod: &mut ItemMod) -> Option<Vec<&mut ItemImpl>> {
-    return root_mod.content.as_mut()
+    root_mod.content.as_mut()
         .map(|(_, items)| items)
         .map(|items| {
             items.iter_mut().filter_map(|i| {
@@ -123,7 +146,7 @@ pub fn extract_impl_blocks(root_mod: &mut ItemMod) -> Option<Vec<&mut ItemImpl>>
                 }
                 None
             }).collect::<Vec<&mut ItemImpl>>()
-        });
+        })
 }
 
 //If trait_ is not present then return self_ty
@@ -163,6 +186,74 @@ pub fn parse_expr_as_number(attr_expr: &Expr) -> syn::Result<usize> {
     line_number
 }
 
+///Parses `name = "value"` expression used in attribute arguments
+pub fn parse_expr_as_str_assignment(attr_expr: &Expr) -> Option<(String, String)> {
+    if let Expr::Assign(expr_assign) = attr_expr {
+        if let (Expr::Path(name), Expr::Lit(expr_lit)) = (expr_assign.left.deref(), expr_assign.right.deref()) {
+            if let syn::Lit::Str(lit_str) = &expr_lit.lit {
+                return name.path.get_ident().map(|ident| (ident.to_string(), lit_str.value()));
+            }
+        }
+    }
+    None
+}
+
+///Returns name of a single word attribute argument, e.g. `before_return`
+pub fn parse_expr_as_flag(attr_expr: &Expr) -> Option<String> {
+    match attr_expr {
+        Expr::Path(path) => path.path.get_ident().map(|ident| ident.to_string()),
+        _ => None
+    }
+}
+
+///Tokens of the code, with group delimiters as separate tokens
+fn flatten_tokens(stream: TokenStream, tokens: &mut Vec<String>) {
+    for tree in stream {
+        match tree {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                tokens.push(open.to_owned());
+                flatten_tokens(group.stream(), tokens);
+                tokens.push(close.to_owned());
+            }
+            tree => tokens.push(tree.to_string()),
+        }
+    }
+}
+
+///Tokens of the code fragment used as an anchor. `None` when the fragment is not valid Rust tokens, e.g. `transfer(`
+pub fn parse_anchor_tokens(pattern: &str) -> Option<Vec<String>> {
+    let mut tokens = Vec::new();
+    flatten_tokens(pattern.parse::<TokenStream>().ok()?, &mut tokens);
+    tokens.retain(|token| !token.is_empty());
+    (!tokens.is_empty()).then_some(tokens)
+}
+
+///Search for the first top level statement containing given code fragment.
+/// Fragment is matched on token boundaries, so `self.supply` matches `self . supply += 1` but not `self.supply_cap`
+pub fn find_stmt_index(f_target: &ImplItemFn, pattern: &str) -> Option<usize> {
+    let pattern = parse_anchor_tokens(pattern)?;
+    f_target.block.stmts.iter().position(|stmt| {
+        let mut tokens = Vec::new();
+        flatten_tokens(stmt.to_token_stream(), &mut tokens);
+        tokens.retain(|token| !token.is_empty());
+        tokens.windows(pattern.len()).any(|window| window == pattern.as_slice())
+    })
+}
+
+///Index of the statement which returns value from the function - tail expression or the last `return` statement.
+/// If function does not return anything explicitly, index points to the end of the function
+pub fn find_return_index(f_target: &ImplItemFn) -> usize {
+    let stmts = &f_target.block.stmts;
+    stmts.iter().rposition(|stmt| {
+        matches!(stmt, Stmt::Expr(_, None) | Stmt::Expr(Expr::Return(_), _))
+    }).unwrap_or(stmts.len())
+}
 
 pub fn merge_fn_with_start_index(f_target: &mut ImplItemFn, source_block: &Block, start_index: usize) {
     console_log!("Merging function {:?} at index: {:#?}", f_target.sig.ident, start_index);
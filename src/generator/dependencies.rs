@@ -0,0 +1,208 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::Standard;
+use crate::generator::registry::StandardDescriptor;
+use crate::generator::source_parser::ExtensionKind;
+
+/// Describes why given extension is a part of the resolved extension list
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InclusionReason {
+    /// Extension was explicitly selected by the user
+    Requested,
+    /// Extension was added automatically, because another extension depends on it
+    RequiredBy(ExtensionKind),
+}
+
+#[derive(Clone, Debug)]
+pub struct ResolvedExtension {
+    pub kind: ExtensionKind,
+    pub reason: InclusionReason,
+}
+
+#[derive(Debug)]
+pub struct DependencyError(String);
+
+impl DependencyError {
+    pub fn new(message: &str) -> DependencyError {
+        DependencyError(message.to_string())
+    }
+}
+
+impl fmt::Display for DependencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid extension selection: {}", self.0)
+    }
+}
+
+impl Error for DependencyError {}
+
+///Built-in list of extensions that have a fragment for given standard. See [`StandardDescriptor::builtin`]
+pub fn supported_extensions(standard: Standard) -> &'static [ExtensionKind] {
+    match standard {
+        Standard::PSP22 => &[
+            ExtensionKind::Metadata,
+            ExtensionKind::Mintable,
+            ExtensionKind::Burnable,
+            ExtensionKind::Wrapper,
+            ExtensionKind::FlashMint,
+            ExtensionKind::Pausable,
+            ExtensionKind::Capped,
+            ExtensionKind::Ownable,
+            ExtensionKind::AccessControl,
+        ],
+        Standard::PSP34 => &[
+            ExtensionKind::Metadata,
+            ExtensionKind::Mintable,
+            ExtensionKind::Burnable,
+            ExtensionKind::Enumerable,
+            ExtensionKind::Ownable,
+            ExtensionKind::AccessControl,
+        ],
+        Standard::PSP37 => &[
+            ExtensionKind::Metadata,
+            ExtensionKind::Mintable,
+            ExtensionKind::Burnable,
+            ExtensionKind::Batch,
+            ExtensionKind::Enumerable,
+            ExtensionKind::Ownable,
+            ExtensionKind::AccessControl,
+        ],
+    }
+}
+
+///Extensions which have to be merged together with the given one.
+/// For example: burnable extension calls `ensure_owner`, so it requires ownable extension as well.
+pub fn dependencies(standard: Standard, kind: &ExtensionKind) -> &'static [ExtensionKind] {
+    match (standard, kind) {
+        (Standard::PSP22, ExtensionKind::Burnable) => &[ExtensionKind::Ownable],
+        (Standard::PSP22, ExtensionKind::Mintable) => &[ExtensionKind::Ownable],
+        (Standard::PSP22, ExtensionKind::Pausable) => &[ExtensionKind::Mintable, ExtensionKind::Ownable],
+        (Standard::PSP22, ExtensionKind::Capped) => &[ExtensionKind::Mintable, ExtensionKind::Ownable],
+        (Standard::PSP34, ExtensionKind::Burnable) => &[ExtensionKind::Ownable, ExtensionKind::Mintable],
+        (Standard::PSP34, ExtensionKind::Mintable) => &[ExtensionKind::Ownable],
+        (Standard::PSP34, ExtensionKind::Metadata) => &[ExtensionKind::Mintable, ExtensionKind::Ownable],
+        (Standard::PSP34, ExtensionKind::Enumerable) => &[ExtensionKind::Mintable, ExtensionKind::Ownable],
+        (Standard::PSP37, ExtensionKind::Burnable) => &[ExtensionKind::Ownable, ExtensionKind::Mintable],
+        (Standard::PSP37, ExtensionKind::Mintable) => &[ExtensionKind::Ownable],
+        (Standard::PSP37, ExtensionKind::Batch) => &[ExtensionKind::Mintable, ExtensionKind::Burnable, ExtensionKind::Ownable],
+        (Standard::PSP37, ExtensionKind::Metadata) => &[ExtensionKind::Mintable, ExtensionKind::Ownable],
+        (Standard::PSP37, ExtensionKind::Enumerable) => &[ExtensionKind::Mintable, ExtensionKind::Ownable],
+        _ => &[],
+    }
+}
+
+///Extensions which can not be merged together with the given one
+pub fn conflicts(standard: Standard, kind: &ExtensionKind) -> &'static [ExtensionKind] {
+    match (standard, kind) {
+        //Wrapper supply is backed by the underlying token, it can not be minted out of thin air
+        (Standard::PSP22, ExtensionKind::Wrapper) => &[ExtensionKind::Mintable, ExtensionKind::Capped],
+        (Standard::PSP22, ExtensionKind::Mintable) => &[ExtensionKind::Wrapper],
+        (Standard::PSP22, ExtensionKind::Capped) => &[ExtensionKind::Wrapper],
+        //Only one access control model can be used at once
+        (_, ExtensionKind::Ownable) => &[ExtensionKind::AccessControl],
+        (_, ExtensionKind::AccessControl) => &[ExtensionKind::Ownable],
+        _ => &[],
+    }
+}
+
+fn is_resolved(resolved: &[ResolvedExtension], kind: &ExtensionKind) -> bool {
+    resolved.iter().any(|r| &r.kind == kind)
+}
+
+/// Builds the final list of extensions for the requested selection.
+///
+/// Requested extensions keep their order and are followed by the dependencies pulled in
+/// automatically. Every added dependency records the extension which required it.
+/// Selection is rejected when it contains an extension not available for the standard
+/// or two extensions which conflict with each other.
+///
+/// # Examples
+///
+/// ```
+/// use ink_generator::Standard;
+/// use ink_generator::generator::dependencies::{InclusionReason, resolve_extensions};
+/// use ink_generator::generator::registry::StandardDescriptor;
+/// use ink_generator::generator::source_parser::ExtensionKind;
+///
+/// let resolved = resolve_extensions(&StandardDescriptor::builtin(Standard::PSP22), &[ExtensionKind::Burnable]).unwrap();
+/// assert_eq!(resolved[1].kind, ExtensionKind::Ownable);
+/// assert_eq!(resolved[1].reason, InclusionReason::RequiredBy(ExtensionKind::Burnable));
+/// ```
+pub fn resolve_extensions(standard: &StandardDescriptor, requested: &[ExtensionKind]) -> Result<Vec<ResolvedExtension>, DependencyError> {
+    let mut resolved: Vec<ResolvedExtension> = Vec::new();
+
+    for kind in requested {
+        if !is_resolved(&resolved, kind) {
+            resolved.push(ResolvedExtension { kind: kind.clone(), reason: InclusionReason::Requested });
+        }
+    }
+
+    //Resolved list grows while iterating - dependencies of dependencies are processed as well
+    let mut idx = 0;
+    while idx < resolved.len() {
+        let kind = resolved[idx].kind.clone();
+        //Custom extensions do not have to be listed in the registry, dependencies declared by their fragments are resolved once the fragments are loaded
+        if standard.extension(&kind).is_none() && !matches!(kind, ExtensionKind::Custom(_)) {
+            return Err(DependencyError::new(&format!("{} extension is not available for {}", kind.path(), standard.name)));
+        }
+        for dependency in standard.dependencies(&kind) {
+            if !is_resolved(&resolved, dependency) {
+                resolved.push(ResolvedExtension { kind: dependency.clone(), reason: InclusionReason::RequiredBy(kind.clone()) });
+            }
+        }
+        idx += 1;
+    }
+
+    for extension in resolved.iter() {
+        if let Some(conflicting) = standard.conflicts(&extension.kind).iter().find(|c| is_resolved(&resolved, c)) {
+            return Err(DependencyError::new(&format!("{} can not be used together with {}", extension.kind.path(), conflicting.path())));
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Sorts extensions into the canonical merge order.
+///
+/// Every extension is placed after all of its dependencies (see [`StandardDescriptor::dependencies`]).
+/// Extensions which are independent of each other are ordered by [`ExtensionKind::priority`],
+/// so the same selection always produces the same output regardless of the input order.
+/// Selection is rejected when its extensions depend on each other in a cycle.
+///
+/// # Examples
+///
+/// ```
+/// use ink_generator::Standard;
+/// use ink_generator::generator::dependencies::canonical_order;
+/// use ink_generator::generator::registry::StandardDescriptor;
+/// use ink_generator::generator::source_parser::ExtensionKind;
+///
+/// let ordered = canonical_order(&StandardDescriptor::builtin(Standard::PSP22), &[ExtensionKind::Pausable, ExtensionKind::Ownable, ExtensionKind::Mintable]).unwrap();
+/// assert_eq!(ordered, vec![ExtensionKind::Ownable, ExtensionKind::Mintable, ExtensionKind::Pausable]);
+/// ```
+pub fn canonical_order(standard: &StandardDescriptor, kinds: &[ExtensionKind]) -> Result<Vec<ExtensionKind>, DependencyError> {
+    let mut pending: Vec<ExtensionKind> = Vec::new();
+    for kind in kinds {
+        if !pending.contains(kind) {
+            pending.push(kind.clone());
+        }
+    }
+    //Custom extensions share the same priority, so they are ordered by name
+    pending.sort_by(|a, b| a.priority().cmp(&b.priority()).then_with(|| a.path().cmp(&b.path())));
+
+    let mut ordered: Vec<ExtensionKind> = Vec::with_capacity(pending.len());
+    while !pending.is_empty() {
+        //First extension (by priority) which has all of its selected dependencies already ordered
+        let ready_idx = pending.iter().position(|kind| {
+            standard.dependencies(kind).iter().all(|dependency| !pending.contains(dependency))
+        }).ok_or_else(|| {
+            let cycle = pending.iter().map(ExtensionKind::path).collect::<Vec<_>>();
+            DependencyError::new(&format!("dependency cycle between {}", cycle.join(", ")))
+        })?;
+
+        ordered.push(pending.remove(ready_idx));
+    }
+
+    Ok(ordered)
+}
@@ -27,11 +27,12 @@ use crate::ExternalCrate;
 /// version = "0.1.0"
 /// "#.to_owned();
 ///
-/// let updated_cargo_toml = ink_generator::generator::manifest_parser::add_author_and_license(
+/// let updated_cargo_toml = ink_generator::generator::manifest_parser::update_cargo_config(
 ///     cargo_toml,
 ///     Some("MIT".to_owned()),
+///     None,
 /// );
-/// println!("{}", updated_cargo_toml);
+/// assert!(updated_cargo_toml.contains("license = \"MIT\""));
 /// ```
 fn add_author_and_license(
     mut parsed_toml: Document,
@@ -62,8 +63,8 @@ fn add_crate_import(
     external_crate: Option<ExternalCrate>,
 ) -> Document {
     if let Some(external_crate) = external_crate {
-        parsed_toml["dependencies"][external_crate.name]["version"] = value(external_crate.version);
-        parsed_toml["dependencies"][external_crate.name]["default-features"] = value(false);
+        parsed_toml["dependencies"][&external_crate.name]["version"] = value(external_crate.version);
+        parsed_toml["dependencies"][&external_crate.name]["default-features"] = value(false);
     }
 
     parsed_toml
@@ -76,7 +77,7 @@ pub fn update_cargo_config(
 ) -> String {
     cargo_toml.parse::<Document>()
         .map(|doc| add_author_and_license(doc, license))
-        .map(|doc| add_std_features(doc, external_crate.as_ref().map(|ext| String::from(ext.name))))
+        .map(|doc| add_std_features(doc, external_crate.as_ref().map(|ext| ext.name.clone())))
         .map(|doc| add_crate_import(doc, external_crate))
         .map(|doc| doc.to_string())
         .expect("Unable to parse TOML")
@@ -1,38 +1,200 @@
 use std::borrow::BorrowMut;
+use std::collections::HashMap;
 use std::error::Error;
+use std::mem;
+use std::str::FromStr;
 
-use syn::{Attribute, Expr, Field, Fields, File, Ident, ImplItemFn, Item, ItemImpl, ItemMod, ItemStruct, ItemUse, parse_quote, Stmt, UseTree};
+use syn::{Attribute, Block, Expr, ExprStruct, Field, Fields, File, FnArg, Ident, ImplItem, ImplItemFn, Item, ItemImpl, ItemMod, ItemStruct, ItemUse, parse_quote, Pat, ReturnType, Stmt, Type, UseTree};
 use syn::__private::ToTokens;
 use syn::spanned::Spanned;
 
-use crate::{Metadata, Standard};
-use crate::generator::ast::{extract_attribute_expression, extract_fn_by_ident, extract_fn_implementation_by_attr, extract_fn_implementations, extract_impl_blocks, extract_impl_by_ident, field_to_fn_arg, find_attribute, find_struct_by_attr, generate_field_value, get_ident_from_impl_block, merge_fn_with_start_index, parse_expr_as_number};
-use crate::generator::merge::constructor_values::produce_psp22_metadata_field_expr;
-use crate::generator::merge::uses::{extract_uses, has_use};
+use crate::{ConflictPolicy, get_all_files, Metadata, OutputFile, Standard};
+use crate::generator::ast::{extract_attribute_expression, extract_fn_by_ident, extract_fn_implementations, extract_impl_blocks, extract_impl_by_ident, find_attribute, find_return_index, find_stmt_index, find_struct_by_attr, generate_field_value, parse_anchor_tokens, get_ident_from_impl_block, has_mutable_receiver, strip_smart_beaver_attributes, is_ink_constructor, is_ink_message, merge_fn_with_start_index, parse_attribute_args, parse_expr_as_flag, parse_expr_as_name_list, parse_expr_as_number, parse_expr_as_str_assignment};
+use crate::generator::dependencies::{canonical_order, DependencyError};
+use crate::generator::merge::conflicts::{ConflictTracker, FnChange, MergeConflict, MergeConflictError};
+use crate::generator::merge::constructor_params::{ConstructorParam, ConstructorSignature, PendingParams};
+use crate::generator::merge::constructor_values::{ConstructorValues, produce_psp22_metadata_field_expr, produce_psp34_metadata_attributes, produce_psp34_metadata_stmts, produce_psp34_token_uri_fn, value_to_expr};
+use crate::generator::merge::error_variants::{apply_error_variants, ErrorVariants, extract_error_variants, strip_error_variants, strip_extensible_markers};
+use crate::generator::merge::errors::{ConstructorValueError, MergeError};
+use crate::generator::merge::items::merge_items;
+use crate::generator::merge::removal::{has_remove_attribute, parse_removed_params, RemovedItem, validate_removals};
+use crate::generator::merge::report::{MergeReport, push_unique};
+use crate::generator::merge::uses::{extract_uses, has_use, imports_name, remove_use};
+use crate::generator::registry::StandardDescriptor;
 use crate::generator::source_parser::{ExtensionContext, ExtensionKind};
 use crate::logger::console_log;
 use crate::logger::log;
 
 mod uses;
-mod errors;
-mod constructor_values;
+pub mod errors;
+pub mod constructor_values;
+pub mod constructor_params;
+pub mod conflicts;
+pub mod report;
+mod removal;
+mod items;
+pub mod error_variants;
+pub mod extension_descriptor;
 
 const DEFAULT_LINE_NUMBER_VALUE: usize = 0;
 
 pub trait AstMerger {
-    fn merge(base_contract: &File, extensions: Vec<ExtensionContext>, standard: Standard, metadata_common: &Option<Metadata>, single_file_mode: bool) -> Result<File, Box<dyn Error>>;
+    fn merge(base_files: &ContractFiles, extensions: Vec<ExtensionContext>, standard: &StandardDescriptor, metadata_common: &Option<Metadata>, constructor_values: &ConstructorValues, single_file_mode: bool, conflict_policy: ConflictPolicy) -> Result<MergeOutcome, Box<dyn Error>>;
+}
+
+///Parsed contract files, keyed by the output file they are written to. `OutputFile::Main` is always present
+pub type ContractFiles = HashMap<OutputFile, File>;
+
+pub struct MergeOutcome {
+    pub files: ContractFiles,
+    ///Conflicts found during the merge. Can be non-empty only with `ConflictPolicy::Warn`
+    pub conflicts: Vec<MergeConflict>,
+    pub report: MergeReport,
+    ///Error variants which could not be merged into the main file, because the error enum lives in the standard's errors file
+    pub error_variants: Vec<ErrorVariants>,
+    ///Final signature of every constructor
+    pub constructors: Vec<ConstructorSignature>,
+}
+
+impl MergeOutcome {
+    pub fn main_file(&self) -> &File {
+        &self.files[&OutputFile::Main]
+    }
 }
 
 pub type FnChangesCount = u32;
 
 pub struct Merger;
 
+///State collected while merging extensions one by one
+#[derive(Default)]
+struct MergeState {
+    conflicts: ConflictTracker,
+    report: MergeReport,
+    removed: Vec<(ExtensionKind, RemovedItem)>,
+    params: PendingParams,
+    ///Constructor values supplied by the caller which were baked into constructors
+    used_values: Vec<String>,
+    ///Types of PSP34 metadata fields, which have to implement `set_attribute`
+    metadata_types: Vec<Type>,
+}
+
+impl MergeState {
+    fn record_removal(&mut self, extension_kind: &ExtensionKind, item: RemovedItem) {
+        self.report.extension_mut(extension_kind).removed.push(item.to_string());
+        self.removed.push((extension_kind.clone(), item));
+    }
+
+    fn record_function(&mut self, impl_ident: &str, name: &str, extension_kind: &ExtensionKind, change: FnChange) {
+        self.conflicts.record_function(impl_ident, name, extension_kind, change);
+
+        let report = self.report.extension_mut(extension_kind);
+        let qualified_name = format!("{impl_ident}::{name}");
+        match change {
+            FnChange::Copied => report.functions_copied.push(qualified_name),
+            FnChange::Appended => report.functions_appended.push(qualified_name),
+            FnChange::Replaced => report.functions_replaced.push(qualified_name),
+            FnChange::Guarded => report.functions_guarded.push(qualified_name),
+            FnChange::Removed | FnChange::Duplicated => {}
+        }
+    }
+}
+
+
+///Place in the target function where appended statements are inserted
+enum AppendPosition {
+    ///Statement index - `#[smart_beaver::append(line = 1)]`
+    Line(usize),
+    ///Before the first statement containing given code - `#[smart_beaver::append(before = "self.data.transfer")]`
+    Before(String),
+    ///After the first statement containing given code - `#[smart_beaver::append(after = "self.data.transfer")]`
+    After(String),
+    ///Before the value returned from the function - `#[smart_beaver::append(before_return)]`
+    BeforeReturn,
+}
+
+///Defines what happens when function with merge strategy does not exist in the target impl block -
+/// `#[smart_beaver::append(on_missing = "skip")]`
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OnMissing {
+    ///Function from the extension is copied as a new function (default)
+    Copy,
+    ///Merge fails
+    Error,
+    ///Function is ignored, warning is added to the merge report
+    Skip,
+}
 
 enum FnMergeStrategy {
-    Append { line: usize },
-    Replace,
+    Append { position: AppendPosition, on_missing: OnMissing },
+    Replace { on_missing: OnMissing },
+}
+
+impl FnMergeStrategy {
+    fn on_missing(&self) -> OnMissing {
+        match self {
+            FnMergeStrategy::Append { on_missing, .. } | FnMergeStrategy::Replace { on_missing } => *on_missing,
+        }
+    }
+}
+
+///Messages selected by `#[smart_beaver::guard]` attribute
+enum GuardSelector {
+    ///Listed messages - `#[smart_beaver::guard(messages = [transfer, approve])]`
+    Messages(Vec<String>),
+    ///All `#[ink(message)]` functions taking `&mut self` - `#[smart_beaver::guard(mutable_messages)]`
+    MutableMessages,
+}
+
+///Statements injected at the beginning of every selected message
+struct FnGuard {
+    name: String,
+    selector: GuardSelector,
+    except: Vec<String>,
+    block: Block,
+}
+
+
+pub(crate) fn extension_attr() -> Attribute {
+    parse_quote!(#[smart_beaver::extension])
+}
+
+pub(crate) fn is_extension_mod(item_mod: &ItemMod) -> bool {
+    item_mod.attrs.iter().any(|attr| attr.path() == extension_attr().path())
 }
 
+///File extended by the mod - `#[smart_beaver::extension(file = "traits.rs")]`. Defaults to the contract file
+fn extension_target(extension: &ItemMod) -> Result<OutputFile, MergeError> {
+    let file_name = extension.attrs.iter()
+        .filter(|attr| attr.path() == extension_attr().path())
+        .flat_map(parse_attribute_args)
+        .filter_map(|expr| parse_expr_as_str_assignment(&expr))
+        .find_map(|(name, value)| (name == "file").then_some(value));
+
+    match file_name {
+        None => Ok(OutputFile::Main),
+        Some(file_name) => match OutputFile::from_str(&file_name) {
+            Ok(OutputFile::Cargo) | Err(_) => Err(MergeError::new(&format!("Unsupported extension target file: {}", file_name))),
+            Ok(output_file) => Ok(output_file),
+        }
+    }
+}
+
+///Files extended by the extension fragment, so only files which are actually changed have to be loaded and parsed
+pub fn target_files(extension: &File) -> Result<Vec<OutputFile>, MergeError> {
+    let mut targets = Vec::new();
+    for item in &extension.items {
+        if let Item::Mod(item_mod) = item {
+            if is_extension_mod(item_mod) {
+                let target = extension_target(item_mod)?;
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+        }
+    }
+    Ok(targets)
+}
 
 fn parse_main_mod(root: &mut File, attr: Attribute) -> &mut ItemMod {
     match root.items.iter_mut().find_map(|i| {
@@ -53,20 +215,32 @@ fn parse_main_mod(root: &mut File, attr: Attribute) -> &mut ItemMod {
     }
 }
 
-fn merge_imports(base_contract: &mut ItemMod, extension: &ItemMod) {
+fn merge_imports(base_contract: &mut ItemMod, extension: &ItemMod, state: &mut MergeState, extension_kind: &ExtensionKind) -> Result<(), MergeError> {
     // Moving uses from extension to main contract
     let all_children = extension.content.as_ref().unwrap().1.to_vec();
     let uses: Vec<&ItemUse> = extract_uses(&all_children);
 
     if let Some(content) = base_contract.content.as_mut() {
         for single_use in uses {
+            if has_remove_attribute(&single_use.attrs) {
+                let removed_names = remove_use(&mut content.1, single_use).map_err(|e| MergeError::new(&e.to_string()))?;
+                if removed_names.is_empty() {
+                    return Err(MergeError::new(&format!("Removed use {} not found", single_use.tree.to_token_stream())));
+                }
+                removed_names.into_iter().for_each(|name| state.record_removal(extension_kind, RemovedItem::Use { name }));
+                continue;
+            }
+
             if !has_use(&content.1.to_vec(), single_use.clone()).unwrap() {
                 let token_stream = single_use.to_token_stream();
+                let use_path = single_use.tree.to_token_stream().to_string().replace(' ', "");
+                state.report.extension_mut(extension_kind).uses.push(use_path);
                 let item = syn::parse2::<Item>(token_stream).unwrap();
                 content.1.insert(0, item);
             }
         }
     }
+    Ok(())
 }
 
 fn export_fields_from_struct(input_struct: &ItemStruct) -> Vec<Field> {
@@ -79,111 +253,393 @@ fn strip_field_attributes(field: &Field) -> Field {
     new_field
 }
 
-fn append_fields_to_struct(target_struct: &mut ItemStruct, fields: &[Field]) {
+fn append_fields_to_struct(target_struct: &mut ItemStruct, fields: &[Field], state: &mut MergeState, extension_kind: &ExtensionKind) {
     match target_struct.fields.borrow_mut() {
         Fields::Named(named_fields) => {
-            fields.iter().cloned().for_each(|field| {
-                named_fields.named.push(strip_field_attributes(&field));
+            fields.iter().for_each(|field| {
+                if let Some(ident) = field.ident.as_ref() {
+                    let exists = named_fields.named.iter().any(|f| f.ident.as_ref() == Some(ident));
+                    state.conflicts.record_field(&ident.to_string(), extension_kind, exists);
+                    state.report.extension_mut(extension_kind).storage_fields.push(ident.to_string());
+                }
+                named_fields.named.push(strip_field_attributes(field));
             });
         }
         _ => console_log!("Only named fields are supported, ignoring")
     }
 }
 
-fn append_params_to_fn(impl_fn: &mut ImplItemFn, appended_fields: &[Field], search_attr: &Attribute) {
-    for field in appended_fields {
+fn remove_fields_from_struct(target_struct: &mut ItemStruct, fields: &[Field], state: &mut MergeState, extension_kind: &ExtensionKind) -> Result<(), MergeError> {
+    if let Fields::Named(named_fields) = target_struct.fields.borrow_mut() {
+        for ident in fields.iter().filter_map(|field| field.ident.as_ref()) {
+            if !named_fields.named.iter().any(|f| f.ident.as_ref() == Some(ident)) {
+                return Err(MergeError::new(&format!("Removed storage field {} not found", ident)));
+            }
+            named_fields.named = named_fields.named.iter().filter(|f| f.ident.as_ref() != Some(ident)).cloned().collect();
+            state.record_removal(extension_kind, RemovedItem::Field { name: ident.to_string() });
+        }
+    }
+    Ok(())
+}
 
-        //If field contains expression inside attribute ignore it
-        if extract_attribute_expression(&field.attrs, search_attr).is_some() {
-            continue;
+fn is_param_named(fn_arg: &FnArg, name: &Ident) -> bool {
+    matches!(fn_arg, FnArg::Typed(pat_type) if matches!(pat_type.pat.as_ref(), Pat::Ident(pat_ident) if &pat_ident.ident == name))
+}
+
+///Drop params from function signature. Returns names of params which were removed
+fn remove_params_from_fn(impl_fn: &mut ImplItemFn, names: &[Ident]) -> Vec<String> {
+    let mut removed = Vec::new();
+    impl_fn.sig.inputs = impl_fn.sig.inputs.iter().filter(|fn_arg| {
+        match names.iter().find(|name| is_param_named(fn_arg, name)) {
+            Some(name) => {
+                removed.push(name.to_string());
+                false
+            }
+            None => true,
         }
+    }).cloned().collect();
+    removed
+}
+
+///Remove initialization of removed fields from every constructor, together with params of the same name.
+/// Storage literal can be returned directly or bound with `let` first
+fn remove_fields_from_constructors(root_mod: &mut ItemMod, fields: &[Field], state: &mut MergeState, extension_kind: &ExtensionKind) {
+    let idents = fields.iter().filter_map(|field| field.ident.clone()).collect::<Vec<Ident>>();
+
+    for impl_block in extract_impl_blocks(root_mod).unwrap_or_default() {
+        for constructor in extract_fn_implementations(impl_block).into_iter().filter(|f| is_ink_constructor(f)) {
+            for stmt in constructor.block.stmts.iter_mut() {
+                let literal = match stmt {
+                    Stmt::Expr(Expr::Struct(inner_struct), _) => Some(inner_struct),
+                    Stmt::Local(local) => match local.init.as_mut().map(|init| init.expr.as_mut()) {
+                        Some(Expr::Struct(inner_struct)) => Some(inner_struct),
+                        _ => None
+                    },
+                    _ => None
+                };
+                if let Some(inner_struct) = literal {
+                    inner_struct.fields = inner_struct.fields.iter()
+                        .filter(|field_value| !idents.iter().any(|ident| field_value.member == parse_quote!(#ident)))
+                        .cloned()
+                        .collect();
+                }
+            }
 
-        let fn_arg_opt = field_to_fn_arg(field);
-        match fn_arg_opt {
-            None => console_log!("Field has no identifier, ignoring"),
-            Some(fn_arg) => {
-                console_log!("Appending params to: {}", impl_fn.sig.ident);
-                impl_fn.sig.inputs.push(fn_arg);
+            let function = constructor.sig.ident.to_string();
+            for name in remove_params_from_fn(constructor, &idents) {
+                state.record_removal(extension_kind, RemovedItem::Param { function: function.clone(), name });
             }
         }
     }
+
+    //Params added by previously merged extensions are not in signatures yet
+    for (function, name) in state.params.remove_fields(&idents) {
+        state.record_removal(extension_kind, RemovedItem::Param { function, name });
+    }
 }
 
+///Name of the binding used when statements have to run on the constructed storage
+const CONSTRUCTOR_INSTANCE: &str = "instance";
 
-fn extend_constructor_body(
-    impl_fn: &mut ImplItemFn,
-    appended_fields: &[Field],
-    search_attr: &Attribute,
-    extension_kind: &ExtensionKind,
-    standard: Standard,
-    metadata_common: &Option<Metadata>,
-) {
-    let self_block_opt = impl_fn.block.stmts.iter_mut().find(|stmt| matches!(stmt, Stmt::Expr(_, _)));
-    match self_block_opt {
-        None => console_log!("No self block found in constructor"),
-        Some(self_block) => {
-            if let Stmt::Expr(Expr::Struct(inner_struct), _) = self_block {
-                for field in appended_fields {
-                    let new_field_ident = field.ident.as_ref().unwrap();
-                    let custom_expression_opt = extract_attribute_expression(&field.attrs, search_attr);
-
-                    console_log!("Pushing field {:#?} to constructor", new_field_ident);
-
-                    let custom_expression_opt = match extension_kind {
-                        ExtensionKind::Metadata => {
-                            match standard {
-                                Standard::PSP22 => {
-                                    //@TODO refactor to strategy - eliminate unnecessary match statements
-                                    Some(produce_psp22_metadata_field_expr(extension_kind, field, metadata_common))
-                                }
-                                _ => custom_expression_opt
-                            }
-                        }
-                        _ => {
-                            custom_expression_opt//Leave unchanged
-                        }
-                    };
+fn is_storage_literal(inner_struct: &ExprStruct, storage_ident: &Ident) -> bool {
+    inner_struct.path.is_ident("Self") || inner_struct.path.is_ident(storage_ident)
+}
 
-                    inner_struct.fields.push(generate_field_value(new_field_ident, custom_expression_opt));
+///Storage struct literal returned by the constructor - `Self { .. }` or `Storage { .. }` as the last expression,
+/// or bound by `let mut instance = Self { .. };` when the constructor returns `instance`
+fn constructor_self_literal<'a>(impl_fn: &'a mut ImplItemFn, storage_ident: &Ident) -> Option<&'a mut ExprStruct> {
+    let returns_instance = matches!(impl_fn.block.stmts.last(), Some(Stmt::Expr(Expr::Path(path), None)) if path.path.is_ident(CONSTRUCTOR_INSTANCE));
+
+    if returns_instance {
+        return impl_fn.block.stmts.iter_mut().rev().find_map(|stmt| match stmt {
+            Stmt::Local(local) if matches!(&local.pat, Pat::Ident(pat_ident) if pat_ident.ident == CONSTRUCTOR_INSTANCE) => {
+                match local.init.as_mut().map(|init| init.expr.as_mut()) {
+                    Some(Expr::Struct(inner_struct)) if is_storage_literal(inner_struct, storage_ident) => Some(inner_struct),
+                    _ => None
                 }
             }
-        }
+            _ => None
+        });
+    }
+
+    match impl_fn.block.stmts.last_mut() {
+        Some(Stmt::Expr(Expr::Struct(inner_struct), None)) if is_storage_literal(inner_struct, storage_ident) => Some(inner_struct),
+        _ => None
     }
 }
 
-///Iterate over all implementation blocks and search for function tagged with `#[ink(constructor)]` attribute
+///Inserts statements after the storage is constructed. Constructor returning the literal directly is rewritten
+/// to bind it to `instance` first, so the statements can access the storage
+fn append_constructor_stmts(impl_fn: &mut ImplItemFn, stmts: &[Stmt]) {
+    if stmts.is_empty() {
+        return;
+    }
+
+    if let Some(Stmt::Expr(Expr::Struct(_), None)) = impl_fn.block.stmts.last() {
+        let Some(Stmt::Expr(literal, None)) = impl_fn.block.stmts.pop() else {
+            unreachable!("Last statement is the storage literal");
+        };
+        let instance = Ident::new(CONSTRUCTOR_INSTANCE, literal.span());
+        impl_fn.block.stmts.push(parse_quote!(let mut #instance = #literal;));
+        impl_fn.block.stmts.push(Stmt::Expr(parse_quote!(#instance), None));
+    }
+
+    let idx = impl_fn.block.stmts.len() - 1;
+    impl_fn.block.stmts.splice(idx..idx, stmts.iter().cloned());
+}
+
+fn extend_constructor_body(impl_fn: &mut ImplItemFn, initialisers: &[(Ident, Option<Expr>)], storage_ident: &Ident) -> Result<(), MergeError> {
+    let constructor_name = impl_fn.sig.ident.to_string();
+    let inner_struct = constructor_self_literal(impl_fn, storage_ident)
+        .ok_or_else(|| MergeError::new(&format!("Constructor {} does not end with a `Self {{ .. }}` literal. \
+            Add it to `#[smart_beaver::storage(except = [..])]` if it should not be extended", constructor_name)))?;
+
+    for (new_field_ident, custom_expression_opt) in initialisers {
+        console_log!("Pushing field {:#?} to constructor", new_field_ident);
+        inner_struct.fields.push(generate_field_value(new_field_ident, custom_expression_opt.clone()));
+    }
+    Ok(())
+}
+
+///Values supplied by the caller which end up in constructors
+struct ConstructorInput<'a> {
+    metadata: &'a Option<Metadata>,
+    values: &'a ConstructorValues,
+}
+
+///Iterate over all implementation blocks and extend every function tagged with `#[ink(constructor)]` attribute.
+/// Constructors listed in `excluded` (e.g. delegating to another constructor) are left unchanged
 fn extend_constructor(
     root_mod: &mut ItemMod,
     appended_fields: &[Field],
+    excluded: &[String],
     extension_kind: &ExtensionKind,
-    standard: Standard,
-    metadata_common: &Option<Metadata>,
-) {
-    //find base contract constructor
-    //add fields as parameters to constructor
-
-    match extract_impl_blocks(root_mod) {
-        None => console_log!("No impl blocks found in base contract"),
-        Some(impl_blocks) => {
-            for impl_block in impl_blocks {
-                match extract_fn_implementation_by_attr(impl_block, parse_quote!(#[ink(constructor)])) {
-                    None => console_log!("No constructor found in base contract"),
-                    Some(fn_item) => {
-                        let init_attribute: Attribute = parse_quote!(#[smart_beaver::init]);
-
-                        match extension_kind {
-                            ExtensionKind::Metadata => {}//pass
-                            _ => {
-                                //Any other extension
-                                append_params_to_fn(fn_item, appended_fields, &init_attribute);
-                            }
-                        }
-                        extend_constructor_body(fn_item, appended_fields, &init_attribute, extension_kind, standard, metadata_common);
-                    }
+    rules: Option<Standard>,
+    input: &ConstructorInput,
+    state: &mut MergeState,
+) -> Result<(), MergeError> {
+    //find base contract constructors
+    //add fields as parameters to every constructor
+    if appended_fields.is_empty() {
+        return Ok(());
+    }
+
+    let storage_ident = match find_struct_by_attr(root_mod, parse_quote!(#[ink(storage)])) {
+        Some(storage) => storage.ident.clone(),
+        None => return Ok(()),
+    };
+    let init_attribute: Attribute = parse_quote!(#[smart_beaver::init]);
+
+    //Field is initialised with (in order of precedence): value supplied by the caller, `#[smart_beaver::init(expr)]` expression
+    //or a new constructor param
+    let mut params = Vec::new();
+    let mut initialisers = Vec::new();
+    for field in appended_fields {
+        let Some(field_ident) = field.ident.clone() else {
+            console_log!("Field has no identifier, ignoring");
+            continue;
+        };
+        let field_name = field_ident.to_string();
+
+        let expr = match (extension_kind, rules) {
+            (ExtensionKind::Metadata, Some(Standard::PSP22)) if input.values.contains_key(&field_name) => {
+                return Err(MergeError::new(&ConstructorValueError::new(&format!(
+                    "{} is a PSP22 metadata field, it is filled from metadata, not constructor values", field_name
+                )).to_string()));
+            }
+            (ExtensionKind::Metadata, Some(Standard::PSP22)) => {
+                //@TODO refactor to strategy - eliminate unnecessary match statements
+                Some(produce_psp22_metadata_field_expr(extension_kind, field, input.metadata))
+            }
+            _ if input.values.contains_key(&field_name) => {
+                let expr = value_to_expr(&field_name, &field.ty, &input.values[&field_name])
+                    .map_err(|e| MergeError::new(&e.to_string()))?;
+                state.report.extension_mut(extension_kind).constructor_values.push(field_name.clone());
+                state.used_values.push(field_name);
+                Some(expr)
+            }
+            _ => match extract_attribute_expression(&field.attrs, &init_attribute) {
+                Some(expr) => Some(expr),
+                //Metadata fields are not constructor params, only PSP22 metadata is filled from the metadata supplied by the user
+                None if *extension_kind == ExtensionKind::Metadata => {
+                    return Err(MergeError::new(&format!("Metadata field {} has to be initialised with #[smart_beaver::init(..)]", field_name)));
+                }
+                None => {
+                    let param = ConstructorParam::from_field(field, extension_kind.clone())?.expect("Field has identifier");
+                    let expr = param.field_value_expr();
+                    params.push(param);
+                    expr
                 }
             }
+        };
+        initialisers.push((field_ident, expr));
+    }
+
+    //PSP34 collection attributes are set on the constructed storage, in the field marked with `#[smart_beaver::metadata]`.
+    //PSP37 attributes belong to single tokens, so there is nothing to set when the contract is constructed
+    let (metadata_stmts, token_uri) = match (extension_kind, rules) {
+        (ExtensionKind::Metadata, Some(Standard::PSP34)) => psp34_metadata(root_mod, appended_fields, input.metadata, state)?,
+        (ExtensionKind::Metadata, Some(Standard::PSP37)) if input.metadata.is_some() => {
+            push_unique(&mut state.report.extension_mut(extension_kind).warnings, "PSP37 has no collection metadata, supplied metadata is ignored".to_owned());
+            (Vec::new(), None)
         }
+        _ => (Vec::new(), None)
     };
+
+    for impl_block in extract_impl_blocks(root_mod).unwrap_or_default() {
+        let mut has_constructor = false;
+        for fn_item in extract_fn_implementations(impl_block) {
+            if !is_ink_constructor(fn_item) {
+                continue;
+            }
+            has_constructor = true;
+            if excluded.contains(&fn_item.sig.ident.to_string()) {
+                console_log!("Constructor {} excluded from extending", fn_item.sig.ident);
+                continue;
+            }
+
+            //Params are appended to the signature after all extensions are merged
+            for param in &params {
+                console_log!("Appending param {} to: {}", param.name, fn_item.sig.ident);
+                push_unique(&mut state.report.extension_mut(extension_kind).constructor_params, param.name.to_string());
+                state.params.push(&fn_item.sig.ident.to_string(), param.clone());
+            }
+            extend_constructor_body(fn_item, &initialisers, &storage_ident)?;
+            append_constructor_stmts(fn_item, &metadata_stmts);
+        }
+
+        if let Some(token_uri) = token_uri.as_ref().filter(|_| has_constructor) {
+            if extract_fn_by_ident(impl_block, &token_uri.sig.ident).is_none() {
+                console_log!("Adding token_uri message");
+                state.report.extension_mut(extension_kind).functions_copied.push(format!("{}::{}", storage_ident, token_uri.sig.ident));
+                impl_block.items.push(ImplItem::Fn(token_uri.clone()));
+            }
+        }
+    }
+
+    if !metadata_stmts.is_empty() {
+        ensure_string_import(root_mod);
+    }
+    Ok(())
+}
+
+fn metadata_attr() -> Attribute {
+    parse_quote!(#[smart_beaver::metadata])
+}
+
+///`collection_id` function of the contract together with the trait it implements, if any
+fn find_collection_id(root_mod: &ItemMod) -> Option<(Option<syn::Path>, &ImplItemFn)> {
+    root_mod.content.as_ref()?.1.iter().find_map(|item| match item {
+        Item::Impl(impl_block) => impl_block.items.iter().find_map(|impl_item| match impl_item {
+            ImplItem::Fn(impl_fn) if impl_fn.sig.ident == "collection_id" => Some((impl_block.trait_.as_ref().map(|(_, path, _)| path.clone()), impl_fn)),
+            _ => None
+        }),
+        _ => None
+    })
+}
+
+///Statements setting PSP34 collection attributes and the `token_uri` message, when the URI is a per-token template.
+/// The collection id is taken from `collection_id` of the contract, called by the path of its trait, so the trait does not have to be imported
+fn psp34_metadata(root_mod: &ItemMod, appended_fields: &[Field], metadata: &Option<Metadata>, state: &mut MergeState) -> Result<(Vec<Stmt>, Option<ImplItemFn>), MergeError> {
+    let attributes = produce_psp34_metadata_attributes(metadata);
+    if attributes.is_empty() {
+        return Ok((Vec::new(), None));
+    }
+
+    let (metadata_field, metadata_ty) = appended_fields.iter()
+        .filter(|field| find_attribute(&field.attrs, &metadata_attr()).is_some())
+        .find_map(|field| field.ident.clone().map(|ident| (ident, field.ty.clone())))
+        .ok_or_else(|| MergeError::new("PSP34 metadata extension has no storage field marked with #[smart_beaver::metadata]"))?;
+    let (trait_path, collection_id_fn) = find_collection_id(root_mod)
+        .ok_or_else(|| MergeError::new("PSP34 metadata requires collection_id function implemented by the contract"))?;
+    let collection_id: Expr = match trait_path {
+        Some(trait_path) => parse_quote!(<Self as #trait_path>::collection_id(&instance)),
+        None => parse_quote!(Self::collection_id(&instance)),
+    };
+
+    let token_uri = match attributes.iter().find(|(key, _)| *key == "uri_template") {
+        Some((_, template)) => {
+            let id = match &collection_id_fn.sig.output {
+                ReturnType::Type(_, ty) => match ty.as_ref() {
+                    Type::Path(type_path) => Some(type_path.path.clone()),
+                    _ => None
+                },
+                ReturnType::Default => None,
+            }.ok_or_else(|| MergeError::new("collection_id has to return the token id type"))?;
+            Some(produce_psp34_token_uri_fn(template, &id))
+        }
+        None => None,
+    };
+
+    state.metadata_types.push(metadata_ty);
+    Ok((produce_psp34_metadata_stmts(&metadata_field, &collection_id, &attributes), token_uri))
+}
+
+///Statements added to constructors use `String`, which is not in the prelude of `no_std` contracts
+fn ensure_string_import(root_mod: &mut ItemMod) {
+    if let Some((_, items)) = root_mod.content.as_mut() {
+        if !imports_name(items, "String") {
+            let idx = items.iter().rposition(|item| matches!(item, Item::Use(_))).map(|idx| idx + 1).unwrap_or(0);
+            items.insert(idx, parse_quote!(use ink::prelude::string::String;));
+        }
+    }
+}
+
+///Names of functions implemented for the type in the items. `None` when the items have no impl block of the type
+fn implemented_fns(items: &[Item], ident: &Ident) -> Option<Vec<Ident>> {
+    let mut found: Option<Vec<Ident>> = None;
+    for item in items {
+        let fns = match item {
+            Item::Impl(impl_block) if matches!(impl_block.self_ty.as_ref(), Type::Path(path) if path.path.segments.last().map(|segment| &segment.ident) == Some(ident)) => {
+                Some(impl_block.items.iter().filter_map(|impl_item| match impl_item {
+                    ImplItem::Fn(impl_fn) => Some(impl_fn.sig.ident.clone()),
+                    _ => None
+                }).collect())
+            }
+            Item::Mod(item_mod) => item_mod.content.as_ref().and_then(|(_, items)| implemented_fns(items, ident)),
+            _ => None
+        };
+        if let Some(fns) = fns {
+            found.get_or_insert_with(Vec::new).extend(fns);
+        }
+    }
+    found
+}
+
+///PSP34 metadata types need `set_attribute`. The type can be checked only when one of the merged files implements it,
+/// types of the external crate or files not changed by extensions are not loaded
+fn validate_metadata_types(files: &ContractFiles, types: &[Type]) -> Result<(), MergeError> {
+    for ty in types {
+        let Some(ident) = (match ty {
+            Type::Path(type_path) => type_path.path.segments.last().map(|segment| &segment.ident),
+            _ => None
+        }) else {
+            continue;
+        };
+        let implemented = files.values()
+            .filter_map(|file| implemented_fns(&file.items, ident))
+            .reduce(|mut all, fns| {
+                all.extend(fns);
+                all
+            });
+        if matches!(implemented, Some(fns) if !fns.iter().any(|name| name == "set_attribute")) {
+            return Err(MergeError::new(&format!("PSP34 metadata type {} has no set_attribute function", ident)));
+        }
+    }
+    Ok(())
+}
+
+///Constructors which should not be extended - `#[smart_beaver::storage(except = [new_default])]`
+pub(crate) fn parse_excluded_constructors(extension_storage: &ItemStruct) -> Vec<String> {
+    find_attribute(&extension_storage.attrs, &parse_quote!(#[smart_beaver::storage]))
+        .map(parse_attribute_args)
+        .unwrap_or_default()
+        .iter()
+        .find_map(|expr| match expr {
+            Expr::Assign(assign) if parse_expr_as_flag(&assign.left).as_deref() == Some("except") => parse_expr_as_name_list(&assign.right),
+            _ => None
+        })
+        .unwrap_or_default()
 }
 
 
@@ -191,111 +647,373 @@ fn merge_state_and_constructor(
     root_mod: &mut ItemMod,
     extension: &mut ItemMod,
     extension_kind: &ExtensionKind,
-    standard: Standard,
-    metadata_common: &Option<Metadata>,
-) {
+    rules: Option<Standard>,
+    input: &ConstructorInput,
+    state: &mut MergeState,
+) -> Result<(), MergeError> {
     let target_storage_opt = find_struct_by_attr(root_mod, parse_quote!(#[ink(storage)]));
     let extension_storage_opt = find_struct_by_attr(extension, parse_quote!(#[smart_beaver::storage]));
 
     match (target_storage_opt, extension_storage_opt) {
         //Both values are present
         (Some(target_storage), Some(extension_storage)) => {
-            let extension_fields = export_fields_from_struct(extension_storage);
+            //Fields tagged with `#[smart_beaver::remove]` are dropped from the base contract instead of being appended
+            let (removed_fields, extension_fields): (Vec<Field>, Vec<Field>) = export_fields_from_struct(extension_storage)
+                .into_iter()
+                .partition(|field| has_remove_attribute(&field.attrs));
             console_log!("Extension fields: {:#?}", extension_fields.iter().map(|x| x.ident.clone()).collect::<Vec<_>>());
+            let excluded_constructors = parse_excluded_constructors(extension_storage);
 
-            append_fields_to_struct(target_storage, &extension_fields);
+            remove_fields_from_struct(target_storage, &removed_fields, state, extension_kind)?;
+            append_fields_to_struct(target_storage, &extension_fields, state, extension_kind);
 
-            extend_constructor(root_mod, &extension_fields, extension_kind, standard, metadata_common);
+            remove_fields_from_constructors(root_mod, &removed_fields, state, extension_kind);
+            extend_constructor(root_mod, &extension_fields, &excluded_constructors, extension_kind, rules, input, state)?;
         }
         _ => console_log!("No storage struct found")
     }
+    Ok(())
+}
+
+///Parses single argument of the append attribute. `None` means the argument does not define the position
+fn parse_append_arg(expr: &Expr) -> Result<Option<AppendPosition>, MergeError> {
+    let invalid = || MergeError::new(&format!("Invalid append argument: {}", expr.to_token_stream()));
+    if parse_expr_as_flag(expr).as_deref() == Some("before_return") {
+        return Ok(Some(AppendPosition::BeforeReturn));
+    }
+
+    let Expr::Assign(assign) = expr else {
+        return Err(invalid());
+    };
+    match parse_expr_as_flag(&assign.left).as_deref() {
+        Some("line") => parse_expr_as_number(expr).map(|line| Some(AppendPosition::Line(line))).map_err(|_| invalid()),
+        Some(anchor @ ("before" | "after")) => {
+            let (_, pattern) = parse_expr_as_str_assignment(expr).ok_or_else(invalid)?;
+            if parse_anchor_tokens(&pattern).is_none() {
+                return Err(MergeError::new(&format!("Invalid append anchor: \"{}\"", pattern)));
+            }
+            Ok(Some(match anchor {
+                "before" => AppendPosition::Before(pattern),
+                _ => AppendPosition::After(pattern),
+            }))
+        }
+        Some("on_missing") => Ok(None),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_append_position(args: &[Expr]) -> Result<AppendPosition, MergeError> {
+    let mut position = None;
+    for expr in args {
+        if let Some(parsed) = parse_append_arg(expr)? {
+            if position.replace(parsed).is_some() {
+                return Err(MergeError::new("Append defines more than one position"));
+            }
+        }
+    }
+
+    Ok(position.unwrap_or(AppendPosition::Line(DEFAULT_LINE_NUMBER_VALUE)))
+}
+
+fn parse_on_missing(args: &[Expr]) -> Result<OnMissing, MergeError> {
+    let value = args.iter()
+        .filter_map(parse_expr_as_str_assignment)
+        .find_map(|(name, value)| (name == "on_missing").then_some(value));
+
+    match value.as_deref() {
+        None | Some("copy") => Ok(OnMissing::Copy),
+        Some("error") => Ok(OnMissing::Error),
+        Some("skip") => Ok(OnMissing::Skip),
+        Some(value) => Err(MergeError::new(&format!("Unknown on_missing value: \"{}\", expected \"copy\", \"error\" or \"skip\"", value))),
+    }
+}
+
+///Resolve append position into statement index of the target function
+fn resolve_append_position(f_target: &ImplItemFn, position: &AppendPosition) -> Result<usize, MergeError> {
+    let find_anchor = |pattern: &str| {
+        find_stmt_index(f_target, pattern).ok_or_else(|| MergeError::new(&format!(
+            "Anchor \"{}\" not found in function {}", pattern, f_target.sig.ident
+        )))
+    };
+
+    match position {
+        AppendPosition::Line(line) => Ok(*line),
+        AppendPosition::Before(pattern) => find_anchor(pattern),
+        //Nothing can follow the tail expression, statements appended there would not compile
+        AppendPosition::After(pattern) => match find_anchor(pattern)? {
+            idx if matches!(f_target.block.stmts[idx], Stmt::Expr(_, None)) => Err(MergeError::new(&format!(
+                "Anchor \"{}\" is the tail expression of function {}, use before_return instead", pattern, f_target.sig.ident
+            ))),
+            idx => Ok(idx + 1),
+        },
+        AppendPosition::BeforeReturn => Ok(find_return_index(f_target)),
+    }
 }
 
 ///Parses attributes list and tries to determine merge strategy
-/// "append" as well as "replace" has optional parameter "line" which is defaulted to 0
-/// Having both, "append" and "replace" is invalid
-fn parse_fn_merge_strategy(attributes: &[Attribute]) -> Option<FnMergeStrategy> {
+/// "append" has optional parameter which defines where statements are inserted: `line = N` (defaulted to 0),
+/// `before = "pattern"`, `after = "pattern"` or `before_return`
+/// Both, "append" and "replace" accept optional `on_missing = "copy" | "error" | "skip"` parameter
+/// Having both, "append" and "replace" is invalid. Unknown or malformed arguments are rejected
+fn parse_fn_merge_strategy(attributes: &[Attribute]) -> Result<Option<FnMergeStrategy>, MergeError> {
     let append_attribute: Attribute = parse_quote!(#[smart_beaver::append]);
     let replace_attribute: Attribute = parse_quote!(#[smart_beaver::replace]);
 
     let append_attr_opt = find_attribute(attributes, &append_attribute);
     let replace_attr_opt = find_attribute(attributes, &replace_attribute);
 
-    let parse_line_number = |target_attribute: &Attribute| {
-        match extract_attribute_expression(attributes, target_attribute) {
-            None => DEFAULT_LINE_NUMBER_VALUE,
-            Some(expr) => parse_expr_as_number(&expr).unwrap_or(DEFAULT_LINE_NUMBER_VALUE)
+    match (append_attr_opt, replace_attr_opt) {
+        (Some(append_attr), None) => {
+            let args = parse_attribute_args(append_attr);
+            Ok(Some(FnMergeStrategy::Append { position: parse_append_position(&args)?, on_missing: parse_on_missing(&args)? }))
+        }
+        (None, Some(replace_attr)) => {
+            let args = parse_attribute_args(replace_attr);
+            if let Some(arg) = args.iter().find(|arg| !matches!(parse_expr_as_str_assignment(arg), Some((name, _)) if name == "on_missing")) {
+                return Err(MergeError::new(&format!("Invalid replace argument: {}", arg.to_token_stream())));
+            }
+            Ok(Some(FnMergeStrategy::Replace { on_missing: parse_on_missing(&args)? }))
         }
+        (None, None) => Ok(None),
+        (Some(_), Some(_)) => Err(MergeError::new("Function can not be marked with both append and replace"))
+    }
+}
+
+fn is_guard(attributes: &[Attribute]) -> bool {
+    find_attribute(attributes, &parse_quote!(#[smart_beaver::guard])).is_some()
+}
+
+///Parses `#[smart_beaver::guard(...)]` attribute. Optional `except = [...]` argument excludes messages from the selection.
+/// Unknown or malformed arguments are rejected
+fn parse_fn_guard(impl_fn: &ImplItemFn) -> Result<Option<FnGuard>, MergeError> {
+    let guard_attribute: Attribute = parse_quote!(#[smart_beaver::guard]);
+    let attr = match find_attribute(&impl_fn.attrs, &guard_attribute) {
+        None => return Ok(None),
+        Some(attr) => attr,
     };
 
-    match (append_attr_opt, replace_attr_opt) {
-        (Some(append_attr), None) => {
-            Some(FnMergeStrategy::Append { line: parse_line_number(append_attr) })
+    let invalid_argument = |arg: &Expr| MergeError::new(&format!("Invalid guard argument: {} of function {}", arg.to_token_stream(), impl_fn.sig.ident));
+    let mut selector = None;
+    let mut except = Vec::new();
+    for arg in parse_attribute_args(attr) {
+        match &arg {
+            Expr::Assign(assign) => match (parse_expr_as_flag(&assign.left).as_deref(), parse_expr_as_name_list(&assign.right)) {
+                (Some("messages"), Some(names)) => selector = Some(GuardSelector::Messages(names)),
+                (Some("except"), Some(names)) => except = names,
+                _ => return Err(invalid_argument(&arg)),
+            },
+            _ if parse_expr_as_flag(&arg).as_deref() == Some("mutable_messages") => selector = Some(GuardSelector::MutableMessages),
+            _ => return Err(invalid_argument(&arg)),
+        }
+    }
+
+    match selector {
+        None => Err(MergeError::new(&format!("Guard {} has to define `messages = [...]` or `mutable_messages`", impl_fn.sig.ident))),
+        Some(selector) => Ok(Some(FnGuard {
+            name: impl_fn.sig.ident.to_string(),
+            selector,
+            except,
+            block: impl_fn.block.clone(),
+        })),
+    }
+}
+
+fn is_guarded(guard: &FnGuard, impl_fn: &ImplItemFn) -> bool {
+    let fn_name = impl_fn.sig.ident.to_string();
+    if guard.except.contains(&fn_name) {
+        return false;
+    }
+
+    match &guard.selector {
+        GuardSelector::Messages(messages) => messages.contains(&fn_name),
+        GuardSelector::MutableMessages => is_ink_message(impl_fn) && has_mutable_receiver(impl_fn),
+    }
+}
+
+///Inject guards defined in the extension into matching functions of all impl blocks in the base contract.
+/// Functions copied by the same extension are not guarded, so e.g. `unpause` is not blocked by the pausable guard
+fn apply_guards(base_contract: &mut ItemMod, extension: &mut ItemMod, state: &mut MergeState, extension_kind: &ExtensionKind) -> Result<(), MergeError> {
+    let mut guards = Vec::new();
+    for extension_impl_block in extract_impl_blocks(extension).unwrap_or_default() {
+        for impl_fn in extract_fn_implementations(extension_impl_block) {
+            if let Some(guard) = parse_fn_guard(impl_fn)? {
+                guards.push(guard);
+            }
+        }
+    }
+
+    for guard in guards {
+        let mut guarded_names = Vec::new();
+        for target_impl_block in extract_impl_blocks(base_contract).unwrap_or_default() {
+            let impl_name = get_ident_from_impl_block(target_impl_block).map(|ident| ident.to_string()).unwrap_or_default();
+            let copied_by_extension = state.report.extension_mut(extension_kind).functions_copied.clone();
+
+            for target_fn in extract_fn_implementations(target_impl_block) {
+                let fn_name = target_fn.sig.ident.to_string();
+                if !is_guarded(&guard, target_fn) || copied_by_extension.contains(&format!("{impl_name}::{fn_name}")) {
+                    continue;
+                }
+
+                console_log!("Guarding function {:?} with {}", target_fn.sig.ident, guard.name);
+                merge_fn_with_start_index(target_fn, &guard.block, 0);
+                state.record_function(&impl_name, &fn_name, extension_kind, FnChange::Guarded);
+                guarded_names.push(fn_name);
+            }
         }
-        (None, Some(_)) => {
-            Some(FnMergeStrategy::Replace)
+
+        if let GuardSelector::Messages(messages) = &guard.selector {
+            if let Some(missing) = messages.iter().find(|message| !guarded_names.contains(message) && !guard.except.contains(message)) {
+                return Err(MergeError::new(&format!("Message {} guarded by {} not found", missing, guard.name)));
+            }
         }
-        (None, None) => None,
-        (Some(_), Some(_)) => None
     }
+
+    Ok(())
 }
 
-fn merge_functions(target_impl_block: &mut ItemImpl, extension_impl_block: &mut ItemImpl) -> FnChangesCount {
+fn remove_fn(impl_block: &mut ItemImpl, ident: &Ident) -> bool {
+    let items_count = impl_block.items.len();
+    impl_block.items.retain(|item| !matches!(item, ImplItem::Fn(impl_fn) if &impl_fn.sig.ident == ident));
+    items_count != impl_block.items.len()
+}
+
+fn merge_functions(
+    target_impl_block: &mut ItemImpl,
+    extension_impl_block: &mut ItemImpl,
+    state: &mut MergeState,
+    extension_kind: &ExtensionKind,
+    impl_ident: &str,
+) -> Result<FnChangesCount, MergeError> {
     //Track number of changes made to the target_impl_block
     let mut changes_count: FnChangesCount = 0;
 
     //read all functions from extension
-    extract_fn_implementations(extension_impl_block).iter().for_each(|impl_item| {
+    for impl_item in extract_fn_implementations(extension_impl_block) {
+        //Guards are applied separately, see `apply_guards`
+        if is_guard(&impl_item.attrs) {
+            continue;
+        }
+
+        //`#[smart_beaver::remove]` drops entire function, `#[smart_beaver::remove(params = [...])]` only listed params
+        let removal = has_remove_attribute(&impl_item.attrs).then(|| parse_removed_params(&impl_item.attrs));
+        let fn_name = impl_item.sig.ident.to_string();
+
+        if let Some(None) = removal {
+            if !remove_fn(target_impl_block, &impl_item.sig.ident) {
+                return Err(MergeError::new(&format!("Removed function {}::{} not found", impl_ident, fn_name)));
+            }
+            console_log!("Removing function: {:#?}", impl_item.sig.ident);
+            state.record_function(impl_ident, &fn_name, extension_kind, FnChange::Removed);
+            state.record_removal(extension_kind, RemovedItem::Function { impl_block: impl_ident.to_owned(), name: fn_name });
+            changes_count += 1;
+            continue;
+        }
+
         //read function from base contract by its Ident
         let fn_target_opt = extract_fn_by_ident(target_impl_block, &impl_item.sig.ident);
 
         //Determine merger strategy
-        let extension_fn_merge_strategy = parse_fn_merge_strategy(&impl_item.attrs);
+        let extension_fn_merge_strategy = parse_fn_merge_strategy(&impl_item.attrs)
+            .map_err(|e| MergeError::new(&format!("{} of function {}::{}", e.message(), impl_ident, fn_name)))?;
 
         match fn_target_opt {
+            None if removal.is_some() => {
+                return Err(MergeError::new(&format!("Function {}::{} with removed params not found", impl_ident, fn_name)));
+            }
             None => {
                 //Fn doest not exist in target contract
-                //If attributes are present, function is ignored
-                if extension_fn_merge_strategy.is_none() {
-                    console_log!("Copying function: {:#?}", impl_item.sig.ident);
-                    changes_count += 1;
-                    target_impl_block.items.push(parse_quote!(#impl_item));
-                } else {
-                    console_log!("Merge strategy not supported for new(not existing on the target module) functions");
+                //If merge strategy is present, `on_missing` parameter decides what to do
+                match extension_fn_merge_strategy.map(|strategy| strategy.on_missing()) {
+                    None | Some(OnMissing::Copy) => {
+                        console_log!("Copying function: {:#?}", impl_item.sig.ident);
+                        state.record_function(impl_ident, &fn_name, extension_kind, FnChange::Copied);
+                        changes_count += 1;
+                        let mut copied_fn = impl_item.clone();
+                        copied_fn.attrs = strip_smart_beaver_attributes(&copied_fn.attrs);
+                        target_impl_block.items.push(parse_quote!(#copied_fn));
+                    }
+                    Some(OnMissing::Skip) => {
+                        console_log!("Target function not found, skipping: {:#?}", impl_item.sig.ident);
+                        state.report.extension_mut(extension_kind).warnings
+                            .push(format!("{}::{} skipped - target function not found", impl_ident, fn_name));
+                    }
+                    Some(OnMissing::Error) => {
+                        return Err(MergeError::new(&format!("Target function {}::{} not found", impl_ident, fn_name)));
+                    }
                 }
             }
             Some(f_target) => {
                 //Fn is present in the target contract
                 //Strategy selection is required
                 match extension_fn_merge_strategy {
-                    None => console_log!("No merge strategy found for function: {:#?} - ignoring", f_target.sig.ident),
+                    None if removal.is_some() => {}//Only params are removed
+                    None => {
+                        console_log!("No merge strategy found for function: {:#?} - ignoring", f_target.sig.ident);
+                        state.record_function(impl_ident, &fn_name, extension_kind, FnChange::Duplicated);
+                    }
                     Some(merge_strategy) => {
                         changes_count += 1;
                         match merge_strategy {
-                            FnMergeStrategy::Append { line } => {
+                            FnMergeStrategy::Append { position, .. } => {
                                 console_log!("Merging function: {:#?}", f_target.sig.ident);
+                                let line = resolve_append_position(f_target, &position)?;
+                                state.record_function(impl_ident, &fn_name, extension_kind, FnChange::Appended);
                                 merge_fn_with_start_index(f_target, &impl_item.block, line);
                             }
-                            FnMergeStrategy::Replace => {
+                            FnMergeStrategy::Replace { .. } => {
                                 console_log!("Overriding function: {:#?}", f_target.sig.ident);
+                                state.record_function(impl_ident, &fn_name, extension_kind, FnChange::Replaced);
                                 f_target.block = impl_item.block.clone();
                             }
                         }
                     }
                 }
+
+                if let Some(Some(params)) = removal {
+                    let param_idents = params.iter().map(|param| Ident::new(param, f_target.sig.ident.span())).collect::<Vec<_>>();
+                    for name in remove_params_from_fn(f_target, &param_idents) {
+                        state.record_removal(extension_kind, RemovedItem::Param { function: fn_name.clone(), name });
+                    }
+                    changes_count += 1;
+                }
             }
         }
-    });
-    changes_count
+    }
+    Ok(changes_count)
 }
 
-fn merge_impl_blocks(base_contract: &mut ItemMod, extension: &mut ItemMod) {
+///Remove impl block implementing the same trait for the same type as the given one
+fn remove_impl_block(base_contract: &mut ItemMod, removed_impl_block: &ItemImpl) -> bool {
+    let trait_path = |impl_block: &ItemImpl| impl_block.trait_.as_ref().map(|(_, path, _)| path.clone());
+    match base_contract.content.as_mut() {
+        None => false,
+        Some((_, content)) => {
+            let items_count = content.len();
+            content.retain(|item| !matches!(item, Item::Impl(impl_block)
+                if trait_path(impl_block) == trait_path(removed_impl_block) && impl_block.self_ty == removed_impl_block.self_ty));
+            items_count != content.len()
+        }
+    }
+}
+
+fn merge_impl_blocks(base_contract: &mut ItemMod, extension: &mut ItemMod, state: &mut MergeState, extension_kind: &ExtensionKind) -> Result<(), MergeError> {
     let extension_impl_blocks_opt = extract_impl_blocks(extension);
     match extension_impl_blocks_opt {
         None => console_log!("No impl blocks found in extension"),
         Some(impl_blocks) => {
             for extension_impl_block in impl_blocks {
                 let ident = get_ident_from_impl_block(extension_impl_block).expect("No ident found, impl block must have a name");
+                let impl_name = ident.to_string();
+                let is_trait_impl = extension_impl_block.trait_.is_some();
+
+                if has_remove_attribute(&extension_impl_block.attrs) {
+                    if !remove_impl_block(base_contract, extension_impl_block) {
+                        return Err(MergeError::new(&format!("Removed impl block {} not found", impl_name)));
+                    }
+                    console_log!("Removing impl: {:#?}", ident);
+                    state.record_removal(extension_kind, RemovedItem::Impl { name: impl_name });
+                    continue;
+                }
 
                 let impl_target_opt = extract_impl_by_ident(base_contract, ident);
 
@@ -317,7 +1035,11 @@ fn merge_impl_blocks(base_contract: &mut ItemMod, extension: &mut ItemMod) {
 
                                 extension_impl_block_contentless.items.clear();
 
-                                if merge_functions(&mut extension_impl_block_contentless, &mut extension_impl_block.clone()) > 0 {
+                                if merge_functions(&mut extension_impl_block_contentless, &mut extension_impl_block.clone(), state, extension_kind, &impl_name)? > 0 {
+                                    if is_trait_impl {
+                                        state.conflicts.record_trait_impl(&impl_name, extension_kind, true);
+                                    }
+                                    state.report.extension_mut(extension_kind).impl_blocks.push(impl_name.clone());
                                     content.push(parse_quote!(#extension_impl_block_contentless));
                                 } else {
                                     console_log!("No functions merged, ignoring impl block: {:?}", ident);
@@ -330,82 +1052,199 @@ fn merge_impl_blocks(base_contract: &mut ItemMod, extension: &mut ItemMod) {
                         //impl is being overridden
                         //merge functions one by one
                         console_log!("Merging impl: {:#?}", ident);
-                        merge_functions(target_item_impl, &mut extension_impl_block.clone());
+                        if is_trait_impl {
+                            state.conflicts.record_trait_impl(&impl_name, extension_kind, false);
+                        }
+                        merge_functions(target_item_impl, &mut extension_impl_block.clone(), state, extension_kind, &impl_name)?;
                     }
                 }
             }
         }
     }
+    Ok(())
 }
 
-impl AstMerger for Merger {
-    fn merge(base_contract: &File, extensions: Vec<ExtensionContext>, standard: Standard, metadata_common: &Option<Metadata>, single_file_mode: bool) -> Result<File, Box<dyn Error>> {
-        let mut common = base_contract.clone();
+///Reorder extensions into canonical order, so output does not depend on the order of user selection
+fn sort_extensions(extensions: Vec<ExtensionContext>, standard: &StandardDescriptor) -> Result<Vec<ExtensionContext>, DependencyError> {
+    let kinds = extensions.iter().map(|extension| extension.kind.clone()).collect::<Vec<_>>();
+    let mut extensions = extensions;
+    let mut sorted = Vec::with_capacity(extensions.len());
 
-        filter_global_imports(&mut common, single_file_mode, standard);
+    for kind in canonical_order(standard, &kinds)? {
+        while let Some(idx) = extensions.iter().position(|extension| extension.kind == kind) {
+            sorted.push(extensions.remove(idx));
+        }
+    }
 
-        //Search for main mod blocks
-        let base_main_mod = parse_main_mod(&mut common, parse_quote! {
-            #[ink::contract]
-        });
+    Ok(sorted)
+}
+
+///Top level items of the file wrapped in a mod, so the file can be merged the same way as the contract mod.
+/// Items are moved back with `unwrap_file_items`
+fn wrap_file_items(file: &mut File) -> ItemMod {
+    let mut wrapper: ItemMod = parse_quote!(mod file {});
+    wrapper.content = Some((Default::default(), mem::take(&mut file.items)));
+    wrapper
+}
+
+fn unwrap_file_items(file: &mut File, wrapper: ItemMod) {
+    file.items = wrapper.content.map(|(_, items)| items).unwrap_or_default();
+}
+
+fn merge_extension_mod(
+    base_contract: &mut ItemMod,
+    extension: &mut ItemMod,
+    target: OutputFile,
+    rules: Option<Standard>,
+    input: &ConstructorInput,
+    state: &mut MergeState,
+    extension_kind: &ExtensionKind,
+) -> Result<(), MergeError> {
+    merge_imports(base_contract, extension, state, extension_kind)?;
+
+    //Storage and constructors are defined only by the contract
+    if target == OutputFile::Main {
+        merge_state_and_constructor(base_contract, extension, extension_kind, rules, input, state)?;
+    }
+
+    merge_items(base_contract, extension, &mut state.conflicts, &mut state.report, extension_kind);
 
-        for extension in extensions {
+    merge_impl_blocks(base_contract, extension, state, extension_kind)?;
+
+    apply_guards(base_contract, extension, state, extension_kind)
+}
+
+impl AstMerger for Merger {
+    fn merge(base_files: &ContractFiles, extensions: Vec<ExtensionContext>, standard: &StandardDescriptor, metadata_common: &Option<Metadata>, constructor_values: &ConstructorValues, single_file_mode: bool, conflict_policy: ConflictPolicy) -> Result<MergeOutcome, Box<dyn Error>> {
+        let mut files = base_files.clone();
+        let mut state = MergeState::default();
+        let mut error_variants = Vec::new();
+        let input = ConstructorInput { metadata: metadata_common, values: constructor_values };
+
+        let main_file = files.get_mut(&OutputFile::Main).ok_or_else(|| MergeError::new("Main contract file is missing"))?;
+        filter_global_imports(main_file, single_file_mode, standard);
+
+        for extension in sort_extensions(extensions, standard)? {
             let mut ast = extension.ast.clone();
-            let ext_main_mod = parse_main_mod(&mut ast, parse_quote! {
-                #[smart_beaver::extension]
+            let extension_mods = ast.items.iter_mut().filter_map(|item| match item {
+                Item::Mod(item_mod) if is_extension_mod(item_mod) => Some(item_mod),
+                _ => None
             });
 
-            merge_imports(base_main_mod, ext_main_mod);
+            for ext_mod in extension_mods {
+                let target = extension_target(ext_mod)?;
+                push_unique(&mut state.report.extension_mut(&extension.kind).files, target.to_string());
+
+                if let Some(variants) = extract_error_variants(ext_mod, extension.kind.clone()) {
+                    let report = state.report.extension_mut(&extension.kind);
+                    variants.variants.variants.iter()
+                        .for_each(|variant| report.error_variants.push(format!("{}::{}", variants.variants.ident, variant.ident)));
+                    error_variants.push(variants);
+                    strip_error_variants(ext_mod);
+                }
+
+                let target_file = match files.get_mut(&target) {
+                    Some(target_file) => target_file,
+                    None if single_file_mode => return Err(Box::new(MergeError::new(&format!("{} can not be extended when external crate is used", target)))),
+                    None => return Err(Box::new(MergeError::new(&format!("{} extended by {} is not loaded", target, extension.kind.path())))),
+                };
+
+                if target == OutputFile::Main {
+                    let base_main_mod = parse_main_mod(target_file, parse_quote! {
+                        #[ink::contract]
+                    });
+                    merge_extension_mod(base_main_mod, ext_mod, target, standard.rules, &input, &mut state, &extension.kind)?;
+                } else {
+                    let mut wrapper = wrap_file_items(target_file);
+                    let merged = merge_extension_mod(&mut wrapper, ext_mod, target, standard.rules, &input, &mut state, &extension.kind);
+                    unwrap_file_items(target_file, wrapper);
+                    merged?;
+                }
+            }
+        }
 
-            merge_state_and_constructor(
-                base_main_mod,
-                ext_main_mod,
-                &extension.kind,
-                standard,
-                metadata_common,
-            );
+        validate_metadata_types(&files, &state.metadata_types)?;
 
-            merge_impl_blocks(base_main_mod, ext_main_mod);
+        //Error enum is defined in one of the merged files, or it has to be extended in the errors file later
+        let mut pending_error_variants = Vec::new();
+        for variants in error_variants {
+            let mut applied = false;
+            for file in error_enum_search_order() {
+                if let Some(file) = files.get_mut(&file) {
+                    applied = apply_error_variants(&mut file.items, &variants)?;
+                }
+                if applied {
+                    break;
+                }
+            }
+            if !applied {
+                pending_error_variants.push(variants);
+            }
+        }
+        if single_file_mode && !pending_error_variants.is_empty() {
+            return Err(Box::new(MergeError::new("Error enum of the external crate can not be extended with new variants")));
         }
+        files.values_mut().for_each(strip_extensible_markers);
 
+        let base_main_mod = parse_main_mod(files.get_mut(&OutputFile::Main).expect("Main file is checked above"), parse_quote! {
+            #[ink::contract]
+        });
+        let constructors = state.params.apply(base_main_mod)?;
+        let used_values = &state.used_values;
+        if let Some(unknown) = constructor_values.keys().find(|name| !used_values.contains(name)) {
+            return Err(Box::new(ConstructorValueError::new(&format!("{} is not a storage field added by selected extensions", unknown))));
+        }
+        validate_removals(base_main_mod, &state.removed)?;
         filter_standard_imports(base_main_mod, single_file_mode, standard);
 
+        let conflicts = state.conflicts.into_conflicts();
+        if !conflicts.is_empty() {
+            match conflict_policy {
+                ConflictPolicy::Fail => return Err(Box::new(MergeConflictError { conflicts })),
+                ConflictPolicy::Warn => conflicts.iter().for_each(|conflict| console_log!("Merge conflict: {}", conflict)),
+            }
+        }
+
         console_log!("Merging done");
-        Ok(common.clone())
+        Ok(MergeOutcome {
+            files,
+            conflicts,
+            report: state.report,
+            error_variants: pending_error_variants,
+            constructors,
+        })
     }
 }
 
+///Files searched for the error enum, so the same file is extended whatever order the files are stored in:
+/// the contract and the errors file first, the rest in the usual order of the files
+fn error_enum_search_order() -> Vec<OutputFile> {
+    let first = [OutputFile::Main, OutputFile::Errors];
+    first.iter().copied().chain(get_all_files().into_iter().filter(|file| !first.contains(file))).collect()
+}
+
 ///Filter out not needed import statements when single file mode is enabled
 /// change required imports to use external crate instead of local files
 /// All changes are applied in place to the main mod
 /// Removes all mod statements before the main mod with the contract
 /// and all use statements
-fn filter_global_imports(main_file: &mut File, single_file_mode: bool, standard: Standard) {
-    if !single_file_mode {
-        return;
-    }
-
-    match standard {
-        Standard::PSP22 => {
-            psp22_filter_global_imports(main_file);
-        }
-        Standard::PSP34 => {
-            //nop
-        }
+fn filter_global_imports(main_file: &mut File, single_file_mode: bool, standard: &StandardDescriptor) {
+    if single_file_mode && standard.contract_mod_only {
+        retain_contract_mod(main_file);
     }
 }
 
-fn psp22_filter_global_imports(main_file: &mut File) {
+fn retain_contract_mod(main_file: &mut File) {
     let contract_attr: Attribute = parse_quote! {#[ink::contract]};
 
     let filtered_items = main_file.items.iter().filter(|&i| {
-        return if let Item::Mod(mod_value) = i {
+        if let Item::Mod(mod_value) = i {
             mod_value.attrs.iter().any(|root_attr| {
                 root_attr.path().eq(contract_attr.path())
             })
         } else {
             false
-        };
+        }
     }).cloned().collect::<Vec<Item>>();
 
     main_file.items = filtered_items;
@@ -414,12 +1253,12 @@ fn psp22_filter_global_imports(main_file: &mut File) {
 ///Replace import paths for files from standard
 /// `use crate::PSP22` should become `use psp22::PSP22` etc.
 /// @TODO simplify this function
-fn filter_standard_imports(main_mod: &mut ItemMod, single_file_mode: bool, standard: Standard) {
+fn filter_standard_imports(main_mod: &mut ItemMod, single_file_mode: bool, standard: &StandardDescriptor) {
     if !single_file_mode {
         return;
     }
 
-    if let Some(external_crate) = standard.get_external_crate_name() {
+    if let Some(external_crate) = &standard.external_crate {
         //Current generator implementation assumes that contract will not have any other special imports
         let main_mod_span = main_mod.span();
         let crate_import_prefix = Ident::new("crate", main_mod_span);
@@ -429,7 +1268,7 @@ fn filter_standard_imports(main_mod: &mut ItemMod, single_file_mode: bool, stand
                 if let Item::Use(ref mut item_use) = item {
                     if let UseTree::Path(path) = &mut item_use.tree {
                         if path.ident == crate_import_prefix {
-                            path.ident = Ident::new(sanitize_crate_name(external_crate.name).as_str(), main_mod_span);
+                            path.ident = Ident::new(sanitize_crate_name(&external_crate.name).as_str(), main_mod_span);
                         }
                     }
                 }
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::generator::source_parser::ExtensionKind;
+
+/// Element of the contract contested by more than one extension
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConflictTarget {
+    Function { impl_block: String, name: String },
+    Field { name: String },
+    Impl { name: String },
+    ///Module level item, e.g. event, enum or const
+    Item { kind: String, name: String },
+}
+
+impl fmt::Display for ConflictTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConflictTarget::Function { impl_block, name } => write!(f, "function {impl_block}::{name}"),
+            ConflictTarget::Field { name } => write!(f, "storage field {name}"),
+            ConflictTarget::Impl { name } => write!(f, "impl block {name}"),
+            ConflictTarget::Item { kind, name } => write!(f, "{kind} {name}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MergeConflict {
+    pub target: ConflictTarget,
+    /// Extensions involved in the conflict, in merge order. Empty entry means the base contract.
+    pub extensions: Vec<Option<ExtensionKind>>,
+    pub reason: String,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let extensions = self.extensions.iter()
+            .map(|e| e.as_ref().map(|kind| kind.path()).unwrap_or_else(|| "base contract".to_owned()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "{} [{}]: {}", self.target, extensions, self.reason)
+    }
+}
+
+#[derive(Debug)]
+pub struct MergeConflictError {
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl fmt::Display for MergeConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Extensions can not be merged safely: ")?;
+        let conflicts = self.conflicts.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        write!(f, "{}", conflicts.join("; "))
+    }
+}
+
+impl Error for MergeConflictError {}
+
+/// The way function was changed by an extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FnChange {
+    Copied,
+    Appended,
+    Replaced,
+    /// Guard statements were inserted at the beginning of the function
+    Guarded,
+    Removed,
+    /// Extension defines a function which is already present, without any merge strategy
+    Duplicated,
+}
+
+impl fmt::Display for FnChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FnChange::Copied => "copied".fmt(f),
+            FnChange::Appended => "appended".fmt(f),
+            FnChange::Replaced => "replaced".fmt(f),
+            FnChange::Guarded => "guarded".fmt(f),
+            FnChange::Removed => "removed".fmt(f),
+            FnChange::Duplicated => "duplicated".fmt(f),
+        }
+    }
+}
+
+impl FnChange {
+    ///Guards are appended statements as far as conflicts are concerned
+    fn as_append(self) -> FnChange {
+        match self {
+            FnChange::Guarded => FnChange::Appended,
+            change => change,
+        }
+    }
+}
+
+///Keeps track of which extension changed what, to detect elements contested by multiple extensions
+#[derive(Default)]
+pub struct ConflictTracker {
+    functions: HashMap<(String, String), Vec<(ExtensionKind, FnChange)>>,
+    fields: HashMap<String, ExtensionKind>,
+    impls: HashMap<String, ExtensionKind>,
+    items: HashMap<String, ExtensionKind>,
+    conflicts: Vec<MergeConflict>,
+}
+
+impl ConflictTracker {
+    pub fn record_function(&mut self, impl_block: &str, name: &str, extension: &ExtensionKind, change: FnChange) {
+        let changes = self.functions.entry((impl_block.to_owned(), name.to_owned())).or_default();
+
+        let contested = changes.iter().find(|(other, other_change)| {
+            other != extension && match (change.as_append(), other_change.as_append()) {
+                //Appending to the same function from multiple extensions is fine, e.g. pausable and capped guards
+                (FnChange::Appended, FnChange::Appended) | (FnChange::Appended, FnChange::Copied) => false,
+                (FnChange::Duplicated, FnChange::Appended) => false,
+                _ => true,
+            }
+        }).cloned();
+
+        if let Some((other, other_change)) = contested {
+            let reason = match change {
+                FnChange::Duplicated => "function is defined by more than one extension".to_owned(),
+                _ => format!("{} by {} after being {} by {}", change, extension.path(), other_change, other.path()),
+            };
+            self.conflicts.push(MergeConflict {
+                target: ConflictTarget::Function { impl_block: impl_block.to_owned(), name: name.to_owned() },
+                extensions: vec![Some(other), Some(extension.clone())],
+                reason,
+            });
+        }
+
+        changes.push((extension.clone(), change));
+    }
+
+    ///Register field appended to the storage. `exists` tells if the storage already had field with the same name
+    pub fn record_field(&mut self, name: &str, extension: &ExtensionKind, exists: bool) {
+        if exists {
+            let other = self.fields.get(name).cloned();
+            self.conflicts.push(MergeConflict {
+                target: ConflictTarget::Field { name: name.to_owned() },
+                extensions: vec![other, Some(extension.clone())],
+                reason: "storage field is already defined".to_owned(),
+            });
+        } else {
+            self.fields.insert(name.to_owned(), extension.clone());
+        }
+    }
+
+    ///Register module level item copied from an extension. `exists` tells if the contract already had different item with the same name
+    pub fn record_item(&mut self, kind: &str, name: &str, extension: &ExtensionKind, exists: bool) {
+        if exists {
+            let other = self.items.get(name).cloned();
+            self.conflicts.push(MergeConflict {
+                target: ConflictTarget::Item { kind: kind.to_owned(), name: name.to_owned() },
+                extensions: vec![other, Some(extension.clone())],
+                reason: "item with the same name is already defined".to_owned(),
+            });
+        } else {
+            self.items.insert(name.to_owned(), extension.clone());
+        }
+    }
+
+    ///Register trait implementation merged from an extension. `created` tells if the impl block was copied from the extension.
+    /// Implementing the same trait from two extensions means they take over the same responsibility
+    pub fn record_trait_impl(&mut self, name: &str, extension: &ExtensionKind, created: bool) {
+        if created {
+            self.impls.insert(name.to_owned(), extension.clone());
+            return;
+        }
+
+        if let Some(other) = self.impls.get(name).filter(|other| *other != extension).cloned() {
+            self.conflicts.push(MergeConflict {
+                target: ConflictTarget::Impl { name: name.to_owned() },
+                extensions: vec![Some(other), Some(extension.clone())],
+                reason: "trait is implemented by more than one extension".to_owned(),
+            });
+        }
+    }
+
+    pub fn into_conflicts(self) -> Vec<MergeConflict> {
+        self.conflicts
+    }
+}
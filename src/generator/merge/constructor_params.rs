@@ -0,0 +1,188 @@
+use serde::Serialize;
+use syn::{Attribute, Expr, Field, FnArg, Ident, ItemMod, parse_quote, Pat, Type};
+use syn::__private::ToTokens;
+
+use crate::generator::ast::{extract_fn_implementations, extract_impl_blocks, find_attribute, is_ink_constructor, parse_attribute_args, parse_expr_as_flag, parse_expr_as_number, parse_expr_as_str_assignment};
+use crate::generator::merge::errors::MergeError;
+use crate::generator::source_parser::ExtensionKind;
+
+///Priority of params without `priority` argument
+const DEFAULT_PARAM_PRIORITY: usize = 100;
+
+///Constructor param added for a storage field of an extension.
+/// Declared on the field with `#[smart_beaver::param(name = "initial_owner", priority = 10, required = false, default = Self::env().caller())]`,
+/// all arguments are optional, but a param which is not required needs the `default` expression
+#[derive(Clone, Debug)]
+pub struct ConstructorParam {
+    pub name: Ident,
+    pub ty: Type,
+    ///Params with lower priority come first, params with equal priority keep the merge order
+    pub priority: usize,
+    ///Not required param is passed as `Option<T>` and the field falls back to `default`
+    pub required: bool,
+    pub default: Option<Expr>,
+    pub field: Ident,
+    pub extension: ExtensionKind,
+}
+
+fn param_attr() -> Attribute {
+    parse_quote!(#[smart_beaver::param])
+}
+
+fn parse_bool_assignment(expr: &Expr, name: &str) -> Option<bool> {
+    match expr {
+        Expr::Assign(assign) if parse_expr_as_flag(&assign.left).as_deref() == Some(name) => match assign.right.as_ref() {
+            Expr::Lit(syn::ExprLit { lit: syn::Lit::Bool(lit_bool), .. }) => Some(lit_bool.value),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+fn is_assignment_to(expr: &Expr, name: &str) -> bool {
+    matches!(expr, Expr::Assign(assign) if parse_expr_as_flag(&assign.left).as_deref() == Some(name))
+}
+
+impl ConstructorParam {
+    pub fn from_field(field: &Field, extension: ExtensionKind) -> Result<Option<ConstructorParam>, MergeError> {
+        let Some(field_ident) = field.ident.clone() else {
+            return Ok(None);
+        };
+        let args = find_attribute(&field.attrs, &param_attr()).map(parse_attribute_args).unwrap_or_default();
+
+        let name = args.iter()
+            .filter_map(parse_expr_as_str_assignment)
+            .find_map(|(arg, value)| (arg == "name").then_some(value))
+            .map(|value| Ident::new(&value, field_ident.span()))
+            .unwrap_or_else(|| field_ident.clone());
+        let priority = args.iter()
+            .find(|expr| is_assignment_to(expr, "priority"))
+            .and_then(|expr| parse_expr_as_number(expr).ok())
+            .unwrap_or(DEFAULT_PARAM_PRIORITY);
+        let required = args.iter().find_map(|expr| parse_bool_assignment(expr, "required")).unwrap_or(true);
+        let default = args.iter().find_map(|expr| match expr {
+            Expr::Assign(assign) if is_assignment_to(expr, "default") => Some(assign.right.as_ref().clone()),
+            _ => None
+        });
+
+        match (required, &default) {
+            (false, None) => Err(MergeError::new(&format!("Param {} of {} is not required, but has no default value", name, extension.path()))),
+            (true, Some(_)) => Err(MergeError::new(&format!("Param {} of {} is required, default value is never used", name, extension.path()))),
+            _ => Ok(Some(ConstructorParam { name, ty: field.ty.clone(), priority, required, default, field: field_ident, extension })),
+        }
+    }
+
+    ///Expression initialising the field in the `Self { .. }` literal. `None` means the shorthand `field` syntax
+    pub fn field_value_expr(&self) -> Option<Expr> {
+        let name = &self.name;
+        match (&self.default, self.name == self.field) {
+            (None, true) => None,
+            (None, false) => Some(parse_quote!(#name)),
+            (Some(default), _) => Some(parse_quote!(#name.unwrap_or_else(|| #default))),
+        }
+    }
+
+    pub fn signature(&self) -> SignatureParam {
+        SignatureParam {
+            name: self.name.to_string(),
+            ty: type_name(&self.ty),
+            required: self.required,
+            priority: Some(self.priority),
+            extension: Some(self.extension.path()),
+        }
+    }
+
+    fn to_fn_arg(&self) -> FnArg {
+        let name = &self.name;
+        let ty = &self.ty;
+        match self.required {
+            true => parse_quote!(#name: #ty),
+            false => parse_quote!(#name: Option<#ty>),
+        }
+    }
+}
+
+///Params waiting to be appended to constructors.
+/// Params are appended after all extensions are merged, so the final order depends only on priorities
+#[derive(Default)]
+pub struct PendingParams(Vec<(String, ConstructorParam)>);
+
+impl PendingParams {
+    pub fn push(&mut self, constructor: &str, param: ConstructorParam) {
+        self.0.push((constructor.to_owned(), param));
+    }
+
+    ///Drops params of removed storage fields. Returns `(constructor, param)` names of dropped params
+    pub fn remove_fields(&mut self, fields: &[Ident]) -> Vec<(String, String)> {
+        let (removed, kept) = std::mem::take(&mut self.0).into_iter().partition(|(_, param)| fields.contains(&param.field));
+        self.0 = kept;
+        removed.into_iter().map(|(constructor, param): (String, ConstructorParam)| (constructor, param.name.to_string())).collect()
+    }
+
+    ///Appends params to constructors and returns the final signature of every constructor
+    pub fn apply(self, root_mod: &mut ItemMod) -> Result<Vec<ConstructorSignature>, MergeError> {
+        let mut signatures = Vec::new();
+
+        for impl_block in extract_impl_blocks(root_mod).unwrap_or_default() {
+            for constructor in extract_fn_implementations(impl_block).into_iter().filter(|f| is_ink_constructor(f)) {
+                let constructor_name = constructor.sig.ident.to_string();
+                let mut signature = ConstructorSignature { name: constructor_name.clone(), params: Vec::new() };
+                for input in &constructor.sig.inputs {
+                    if let FnArg::Typed(pat_type) = input {
+                        if let Pat::Ident(pat_ident) = pat_type.pat.as_ref() {
+                            signature.params.push(SignatureParam {
+                                name: pat_ident.ident.to_string(),
+                                ty: type_name(&pat_type.ty),
+                                required: true,
+                                priority: None,
+                                extension: None,
+                            });
+                        }
+                    }
+                }
+
+                let mut params = self.0.iter()
+                    .filter(|(constructor, _)| constructor == &constructor_name)
+                    .map(|(_, param)| param)
+                    .collect::<Vec<_>>();
+                params.sort_by_key(|param| param.priority);
+
+                for param in params {
+                    let param_signature = param.signature();
+                    if signature.params.iter().any(|existing| existing.name == param_signature.name) {
+                        return Err(MergeError::new(&format!("Constructor param {} is added to {} more than once", param_signature.name, constructor_name)));
+                    }
+                    constructor.sig.inputs.push(param.to_fn_arg());
+                    signature.params.push(param_signature);
+                }
+
+                signatures.push(signature);
+            }
+        }
+
+        Ok(signatures)
+    }
+}
+
+fn type_name(ty: &Type) -> String {
+    ty.to_token_stream().to_string().replace(' ', "")
+}
+
+///Param of the generated constructor, as seen by the deployer
+#[derive(Serialize, Debug, Clone)]
+pub struct SignatureParam {
+    pub name: String,
+    pub ty: String,
+    pub required: bool,
+    ///Params added by extensions are ordered by priority, lower first, and come after params of the base contract.
+    /// Empty for params of the base contract
+    pub priority: Option<usize>,
+    ///Extension which added the param. Empty for params of the base contract
+    pub extension: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ConstructorSignature {
+    pub name: String,
+    pub params: Vec<SignatureParam>,
+}
@@ -1,8 +1,16 @@
-use syn::{Expr, Field, parse_quote};
+use std::collections::HashMap;
 
-use crate::{Metadata, Standard};
+use serde_json::Value;
+use syn::{Expr, Field, GenericArgument, Ident, ImplItemFn, LitInt, parse_quote, Path, PathArguments, Stmt, Type};
+use syn::__private::Span;
+
+use crate::Metadata;
+use crate::generator::merge::errors::ConstructorValueError;
 use crate::generator::source_parser::ExtensionKind;
 
+///Values of storage fields supplied at generation time, keyed by field name
+pub type ConstructorValues = HashMap<String, Value>;
+
 //Map extension field to constructor value
 pub fn produce_psp22_metadata_field_expr(
     extension_kind: &ExtensionKind,
@@ -35,4 +43,148 @@ pub fn produce_psp22_metadata_field_expr(
         }
         _ => parse_quote!(0)//We should not get here ;)
     }
-}
\ No newline at end of file
+}
+
+///Placeholder replaced with the token id in per-token URI templates, e.g. `ipfs://bafy../{id}.json`
+pub const PSP34_TOKEN_ID_PLACEHOLDER: &str = "{id}";
+
+///Collection attributes of PSP34 `Metadata` as `(key, value)` pairs.
+/// URI with the `{id}` placeholder is a per-token template and is stored as `uri_template`, any other URI as `base_uri`
+pub fn produce_psp34_metadata_attributes(metadata_opt: &Option<Metadata>) -> Vec<(&'static str, String)> {
+    let Some(metadata) = metadata_opt else {
+        return Vec::new();
+    };
+
+    let uri = metadata.uri.clone().map(|uri| match uri.contains(PSP34_TOKEN_ID_PLACEHOLDER) {
+        true => ("uri_template", uri),
+        false => ("base_uri", uri),
+    });
+
+    vec![
+        metadata.name.clone().map(|name| ("name", name)),
+        metadata.symbol.clone().map(|symbol| ("symbol", symbol)),
+        uri,
+    ].into_iter().flatten().collect()
+}
+
+///`set_attribute` calls storing collection attributes in the metadata field of the constructed `instance`.
+/// `collection_id` is the call returning the id of the collection
+pub fn produce_psp34_metadata_stmts(metadata_field: &Ident, collection_id: &Expr, attributes: &[(&str, String)]) -> Vec<Stmt> {
+    let mut stmts: Vec<Stmt> = vec![parse_quote!(let collection_id = #collection_id;)];
+    stmts.extend(attributes.iter().map(|(key, value)| -> Stmt {
+        parse_quote! {
+            instance.#metadata_field.set_attribute(collection_id.clone(), String::from(#key).into_bytes(), String::from(#value).into_bytes());
+        }
+    }));
+    stmts
+}
+
+///Message rendering the URI of a single token from the template. `id` is the token id type of the contract
+pub fn produce_psp34_token_uri_fn(template: &str, id: &Path) -> ImplItemFn {
+    parse_quote! {
+        #[ink(message)]
+        pub fn token_uri(&self, id: #id) -> String {
+            let id = match id {
+                #id::U8(id) => ink::prelude::format!("{}", id),
+                #id::U16(id) => ink::prelude::format!("{}", id),
+                #id::U32(id) => ink::prelude::format!("{}", id),
+                #id::U64(id) => ink::prelude::format!("{}", id),
+                #id::U128(id) => ink::prelude::format!("{}", id),
+                #id::Bytes(bytes) => bytes.iter().map(|byte| ink::prelude::format!("{:02x}", byte)).collect(),
+            };
+            String::from(#template).replace(#PSP34_TOKEN_ID_PLACEHOLDER, &id)
+        }
+    }
+}
+
+///Last segment of the type path with its generic arguments, e.g. `Option` and `[u128]` for `Option<u128>`
+fn type_segment(ty: &Type) -> Option<(String, Vec<&Type>)> {
+    match ty {
+        Type::Path(type_path) => type_path.path.segments.last().map(|segment| {
+            let generics = match &segment.arguments {
+                PathArguments::AngleBracketed(args) => args.args.iter().filter_map(|arg| match arg {
+                    GenericArgument::Type(ty) => Some(ty),
+                    _ => None
+                }).collect(),
+                _ => Vec::new(),
+            };
+            (segment.ident.to_string(), generics)
+        }),
+        _ => None
+    }
+}
+
+///Upper bound of the unsigned integer type. ink! environment aliases are included
+fn unsigned_max(type_name: &str) -> Option<u128> {
+    match type_name {
+        "u8" => Some(u8::MAX as u128),
+        "u16" => Some(u16::MAX as u128),
+        "u32" | "BlockNumber" => Some(u32::MAX as u128),
+        "u64" | "Timestamp" => Some(u64::MAX as u128),
+        "u128" | "Balance" => Some(u128::MAX),
+        _ => None
+    }
+}
+
+///Integers are accepted as JSON numbers or strings, because JS can not represent every u128 as a number
+fn parse_unsigned(value: &Value) -> Option<u128> {
+    match value {
+        Value::Number(number) => number.as_u64().map(u128::from),
+        Value::String(digits) => digits.parse::<u128>().ok(),
+        _ => None
+    }
+}
+
+///AccountId is expected as a 0x prefixed hex string of 32 bytes
+fn parse_account_id(value: &Value) -> Option<Vec<u8>> {
+    let hex = value.as_str()?.strip_prefix("0x")?;
+    if hex.len() != 64 {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|idx| u8::from_str_radix(&hex[idx..idx + 2], 16).ok()).collect()
+}
+
+///Converts JSON value into an expression of the field type
+/// Supported types: unsigned integers (including `Balance`, `Timestamp` and `BlockNumber`), `bool`, `String`, `AccountId` and `Option` of them
+///
+/// # Examples
+///
+/// ```
+/// use ink_generator::generator::merge::constructor_values::value_to_expr;
+/// use syn::__private::ToTokens;
+///
+/// let expr = value_to_expr("cap", &syn::parse_quote!(Option<Balance>), &serde_json::json!("1000")).unwrap();
+/// assert_eq!(expr.to_token_stream().to_string(), "Some (1000)");
+/// assert!(value_to_expr("cap", &syn::parse_quote!(u8), &serde_json::json!(256)).is_err());
+/// ```
+pub fn value_to_expr(field_name: &str, ty: &Type, value: &Value) -> Result<Expr, ConstructorValueError> {
+    let error = |expected: &str| ConstructorValueError::new(&format!("{} expects {}, got {}", field_name, expected, value));
+    let (type_name, generics) = type_segment(ty).ok_or_else(|| error("supported type"))?;
+
+    if let Some(max) = unsigned_max(&type_name) {
+        return match parse_unsigned(value) {
+            Some(number) if number <= max => Ok(syn::parse_str(&number.to_string()).expect("Integer is a valid expression")),
+            _ => Err(error(&format!("unsigned integer not greater than {}", max))),
+        };
+    }
+
+    match (type_name.as_str(), value) {
+        ("bool", Value::Bool(flag)) => Ok(parse_quote!(#flag)),
+        ("String", Value::String(text)) => Ok(parse_quote!(String::from(#text))),
+        ("AccountId", _) => {
+            let bytes = parse_account_id(value).ok_or_else(|| error("0x prefixed hex string of 32 bytes"))?
+                .into_iter()
+                .map(|byte| LitInt::new(&format!("0x{:02x}", byte), Span::call_site()));
+            Ok(parse_quote!(AccountId::from([#(#bytes),*])))
+        }
+        ("Option", Value::Null) => Ok(parse_quote!(None)),
+        ("Option", _) => {
+            let inner_type = generics.first().ok_or_else(|| error("supported type"))?;
+            let inner = value_to_expr(field_name, inner_type, value)?;
+            Ok(parse_quote!(Some(#inner)))
+        }
+        ("bool", _) => Err(error("boolean")),
+        ("String", _) => Err(error("string")),
+        _ => Err(ConstructorValueError::new(&format!("{} has unsupported type {}", field_name, type_name))),
+    }
+}
@@ -0,0 +1,209 @@
+use std::error::Error;
+
+use syn::{Arm, Attribute, ExprMatch, File, ImplItem, ImplItemFn, Item, ItemEnum, ItemImpl, ItemMod, parse_quote, Pat, Type};
+use syn::__private::ToTokens;
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+
+use crate::generator::ast::{find_attribute, strip_smart_beaver_attributes};
+use crate::generator::merge::errors::MergeError;
+use crate::generator::source_parser::ExtensionKind;
+use crate::prettifier;
+
+///Error variants contributed by a single extension:
+/// ```text
+/// #[smart_beaver::error_variants]
+/// pub enum PSP22Error {
+///     CapExceeded,
+/// }
+///
+/// #[smart_beaver::error_variants]
+/// impl PSP22Error {
+///     fn code(&self) -> u8 {
+///         match self {
+///             PSP22Error::CapExceeded => 10,
+///         }
+///     }
+/// }
+/// ```
+/// Arms of the `match` from the impl block are added to the match marked with `#[smart_beaver::extensible]`
+/// in the function with the same name, implemented for the error enum
+#[derive(Clone, Debug)]
+pub struct ErrorVariants {
+    pub extension: ExtensionKind,
+    pub variants: ItemEnum,
+    pub arms: Vec<ItemImpl>,
+}
+
+fn error_variants_attr() -> Attribute {
+    parse_quote!(#[smart_beaver::error_variants])
+}
+
+fn extensible_attr() -> Attribute {
+    parse_quote!(#[smart_beaver::extensible])
+}
+
+pub fn extract_error_variants(extension: &ItemMod, extension_kind: ExtensionKind) -> Option<ErrorVariants> {
+    let items = &extension.content.as_ref()?.1;
+
+    let variants = items.iter().find_map(|item| match item {
+        Item::Enum(item_enum) if find_attribute(&item_enum.attrs, &error_variants_attr()).is_some() => Some(item_enum.clone()),
+        _ => None
+    })?;
+
+    let arms = items.iter().filter_map(|item| match item {
+        Item::Impl(impl_block) if find_attribute(&impl_block.attrs, &error_variants_attr()).is_some() => Some(impl_block.clone()),
+        _ => None
+    }).collect();
+
+    Some(ErrorVariants { extension: extension_kind, variants, arms })
+}
+
+fn is_error_variants_item(item: &Item) -> bool {
+    match item {
+        Item::Enum(item_enum) => find_attribute(&item_enum.attrs, &error_variants_attr()).is_some(),
+        Item::Impl(impl_block) => find_attribute(&impl_block.attrs, &error_variants_attr()).is_some(),
+        _ => false
+    }
+}
+
+///Removes `#[smart_beaver::error_variants]` items, so they are not copied to the contract as regular items
+pub fn strip_error_variants(extension: &mut ItemMod) {
+    if let Some((_, items)) = extension.content.as_mut() {
+        items.retain(|item| !is_error_variants_item(item));
+    }
+}
+
+fn find_enum<'a>(items: &'a mut [Item], ident: &syn::Ident) -> Option<&'a mut ItemEnum> {
+    items.iter_mut().find_map(|item| match item {
+        Item::Enum(item_enum) if &item_enum.ident == ident => Some(item_enum),
+        Item::Mod(item_mod) => item_mod.content.as_mut().and_then(|(_, items)| find_enum(items, ident)),
+        _ => None
+    })
+}
+
+fn implements(impl_block: &ItemImpl, ident: &syn::Ident) -> bool {
+    match impl_block.self_ty.as_ref() {
+        Type::Path(path) => path.path.segments.last().map(|segment| &segment.ident == ident).unwrap_or(false),
+        _ => false
+    }
+}
+
+fn find_impl_fn<'a>(items: &'a mut [Item], ident: &syn::Ident, fn_name: &syn::Ident) -> Option<&'a mut ImplItemFn> {
+    items.iter_mut().find_map(|item| match item {
+        Item::Impl(impl_block) if implements(impl_block, ident) => impl_block.items.iter_mut().find_map(|impl_item| match impl_item {
+            ImplItem::Fn(impl_fn) if &impl_fn.sig.ident == fn_name => Some(impl_fn),
+            _ => None
+        }),
+        Item::Mod(item_mod) => item_mod.content.as_mut().and_then(|(_, items)| find_impl_fn(items, ident, fn_name)),
+        _ => None
+    })
+}
+
+///Arms of the first `match` in the function
+#[derive(Default)]
+struct MatchArms(Option<Vec<Arm>>);
+
+impl<'ast> Visit<'ast> for MatchArms {
+    fn visit_expr_match(&mut self, expr_match: &'ast ExprMatch) {
+        if self.0.is_none() {
+            self.0 = Some(expr_match.arms.clone());
+        }
+    }
+}
+
+///Inserts arms into matches marked as extensible. Arms are placed before the trailing wildcard arm, if there is one
+struct ExtendMatches<'a> {
+    arms: &'a [Arm],
+    extended: bool,
+}
+
+impl VisitMut for ExtendMatches<'_> {
+    fn visit_expr_match_mut(&mut self, expr_match: &mut ExprMatch) {
+        if find_attribute(&expr_match.attrs, &extensible_attr()).is_some() {
+            let idx = match expr_match.arms.last() {
+                Some(arm) if matches!(arm.pat, Pat::Wild(_)) => expr_match.arms.len() - 1,
+                _ => expr_match.arms.len(),
+            };
+            expr_match.arms.splice(idx..idx, self.arms.iter().cloned());
+            self.extended = true;
+        }
+        syn::visit_mut::visit_expr_match_mut(self, expr_match);
+    }
+}
+
+///Removes `#[smart_beaver::extensible]` markers from matches, so they do not leak into the generated code
+struct StripMarkers;
+
+impl VisitMut for StripMarkers {
+    fn visit_expr_match_mut(&mut self, expr_match: &mut ExprMatch) {
+        expr_match.attrs = strip_smart_beaver_attributes(&expr_match.attrs);
+        syn::visit_mut::visit_expr_match_mut(self, expr_match);
+    }
+}
+
+pub fn strip_extensible_markers(file: &mut File) {
+    StripMarkers.visit_file_mut(file);
+}
+
+///Adds variants and match arms to the error enum found in the items.
+/// Returns `false` when the error enum is not defined in the items
+pub fn apply_error_variants(items: &mut [Item], error_variants: &ErrorVariants) -> Result<bool, MergeError> {
+    let ident = &error_variants.variants.ident;
+    let extension_path = error_variants.extension.path();
+
+    let Some(target_enum) = find_enum(items, ident) else {
+        return Ok(false);
+    };
+
+    for variant in &error_variants.variants.variants {
+        let mut variant = variant.clone();
+        variant.attrs = strip_smart_beaver_attributes(&variant.attrs);
+        match target_enum.variants.iter().find(|target_variant| target_variant.ident == variant.ident) {
+            Some(target_variant) if target_variant.to_token_stream().to_string() == variant.to_token_stream().to_string() => {}
+            Some(_) => return Err(MergeError::new(&format!("Error variant {}::{} added by {} is already defined", ident, variant.ident, extension_path))),
+            None => target_enum.variants.push(variant),
+        }
+    }
+
+    for impl_block in &error_variants.arms {
+        for impl_item in &impl_block.items {
+            let ImplItem::Fn(impl_fn) = impl_item else {
+                continue;
+            };
+            let mut arms = MatchArms::default();
+            arms.visit_block(&impl_fn.block);
+            let Some(arms) = arms.0 else {
+                continue;
+            };
+
+            let fn_name = &impl_fn.sig.ident;
+            let target_fn = find_impl_fn(items, ident, fn_name)
+                .ok_or_else(|| MergeError::new(&format!("Function {}::{} extended by {} not found", ident, fn_name, extension_path)))?;
+            let mut extend_matches = ExtendMatches { arms: &arms, extended: false };
+            extend_matches.visit_block_mut(&mut target_fn.block);
+            if !extend_matches.extended {
+                return Err(MergeError::new(&format!("Function {}::{} has no match marked as extensible", ident, fn_name)));
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+///Adds error variants to the standard's errors file (e.g. `errors.rs`)
+pub fn extend_errors_source(source: &str, error_variants: &[ErrorVariants]) -> Result<String, Box<dyn Error>> {
+    if error_variants.is_empty() && !source.contains("smart_beaver") {
+        return Ok(source.to_owned());
+    }
+
+    let mut file = syn::parse_file(source)?;
+    for variants in error_variants {
+        if !apply_error_variants(&mut file.items, variants)? {
+            return Err(Box::new(MergeError::new(&format!("Error enum {} not found", variants.variants.ident))));
+        }
+    }
+    strip_extensible_markers(&mut file);
+
+    Ok(prettifier::unparse_preserving(source, &file))
+}
@@ -13,3 +13,35 @@ impl std::fmt::Display for ComparisonError {
     }
 }
 impl Error for ComparisonError {}
+
+#[derive(Debug)]
+pub struct MergeError(String);
+impl MergeError {
+    pub fn new(message: &str) -> MergeError {
+        MergeError(message.to_string())
+    }
+
+    pub fn message(&self) -> &str {
+        &self.0
+    }
+}
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Merge failed: {}", self.0)
+    }
+}
+impl Error for MergeError {}
+
+#[derive(Debug)]
+pub struct ConstructorValueError(String);
+impl ConstructorValueError {
+    pub fn new(message: &str) -> ConstructorValueError {
+        ConstructorValueError(message.to_string())
+    }
+}
+impl std::fmt::Display for ConstructorValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Invalid constructor value: {}", self.0)
+    }
+}
+impl Error for ConstructorValueError {}
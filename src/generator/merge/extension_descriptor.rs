@@ -0,0 +1,149 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+use syn::{Expr, File, Item, ItemMod, ItemStruct, parse_quote};
+
+use crate::generator::ast::{extract_attribute_expression, find_attribute, parse_attribute_args, parse_expr_as_flag, parse_expr_as_name_list, parse_expr_as_str_assignment};
+use crate::generator::merge::{extension_attr, is_extension_mod, parse_excluded_constructors, target_files};
+use crate::generator::dependencies::DependencyError;
+use crate::generator::merge::constructor_params::{ConstructorParam, SignatureParam};
+use crate::generator::merge::errors::MergeError;
+use crate::generator::merge::removal::has_remove_attribute;
+use crate::generator::registry::StandardDescriptor;
+use crate::generator::source_parser::ExtensionKind;
+
+///Extension as presented to the user, built from the fragment and the standards registry.
+/// Fragment describes itself with arguments of the extension attribute, all of them are optional:
+/// ```text
+/// #[smart_beaver::extension(title = "Capped supply", description = "Limits the total supply", dependencies = [mintable], standards = [PSP22])]
+/// mod capped { .. }
+/// ```
+#[derive(Serialize, Debug, Clone)]
+pub struct ExtensionDescriptor {
+    ///Path of the extension, used as its identifier, e.g. `security/ownable`
+    pub name: String,
+    ///Defaults to the extension name
+    pub title: String,
+    pub description: Option<String>,
+    pub dependencies: Vec<String>,
+    pub conflicts: Vec<String>,
+    ///Files changed by the extension
+    pub files: Vec<String>,
+    ///Params the extension adds to every constructor, except the ones in `excluded_constructors`.
+    /// Together with the constructors of the base contract they make the signatures before the contract is generated
+    pub constructor_params: Vec<SignatureParam>,
+    ///Constructors of the base contract the extension leaves unchanged
+    pub excluded_constructors: Vec<String>,
+    ///Standards the fragment is written for. Empty when the fragment does not declare them
+    pub standards: Vec<String>,
+}
+
+fn extension_mods(fragment: &File) -> impl Iterator<Item=&ItemMod> {
+    fragment.items.iter().filter_map(|item| match item {
+        Item::Mod(item_mod) if is_extension_mod(item_mod) => Some(item_mod),
+        _ => None
+    })
+}
+
+fn extension_args(fragment: &File) -> Vec<Expr> {
+    extension_mods(fragment)
+        .filter_map(|item_mod| find_attribute(&item_mod.attrs, &extension_attr()))
+        .flat_map(parse_attribute_args)
+        .collect()
+}
+
+fn find_str_arg(args: &[Expr], name: &str) -> Option<String> {
+    args.iter()
+        .filter_map(parse_expr_as_str_assignment)
+        .find_map(|(arg, value)| (arg == name).then_some(value))
+}
+
+fn find_list_arg(args: &[Expr], name: &str) -> Vec<String> {
+    args.iter()
+        .find_map(|expr| match expr {
+            Expr::Assign(assign) if parse_expr_as_flag(&assign.left).as_deref() == Some(name) => parse_expr_as_name_list(&assign.right),
+            _ => None
+        })
+        .unwrap_or_default()
+}
+
+fn parse_kinds(names: &[String]) -> Result<Vec<ExtensionKind>, MergeError> {
+    names.iter()
+        .map(|name| ExtensionKind::from_str(name).map_err(|e| MergeError::new(&e.to_string())))
+        .collect()
+}
+
+fn storage_structs(fragment: &File) -> impl Iterator<Item=&ItemStruct> {
+    extension_mods(fragment)
+        .filter_map(|item_mod| item_mod.content.as_ref())
+        .flat_map(|(_, items)| items)
+        .filter_map(|item| match item {
+            Item::Struct(item_struct) if find_attribute(&item_struct.attrs, &parse_quote!(#[smart_beaver::storage])).is_some() => Some(item_struct),
+            _ => None
+        })
+}
+
+///Params added by fields of `#[smart_beaver::storage]` structs which are not initialised by the fragment itself
+fn constructor_params(fragment: &File, kind: &ExtensionKind) -> Result<Vec<SignatureParam>, MergeError> {
+    //Metadata fields are filled with the metadata supplied by the user
+    if *kind == ExtensionKind::Metadata {
+        return Ok(Vec::new());
+    }
+
+    storage_structs(fragment)
+        .flat_map(|item_struct| &item_struct.fields)
+        .filter(|field| !has_remove_attribute(&field.attrs))
+        .filter(|field| extract_attribute_expression(&field.attrs, &parse_quote!(#[smart_beaver::init])).is_none())
+        .filter_map(|field| ConstructorParam::from_field(field, kind.clone()).transpose())
+        .map(|param| param.map(|param| param.signature()))
+        .collect()
+}
+
+fn merge_names(standard: &StandardDescriptor, registry: &[ExtensionKind], declared: &[ExtensionKind]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for path in registry.iter().chain(declared).map(|kind| standard.extension_path(kind)) {
+        if !names.contains(&path) {
+            names.push(path);
+        }
+    }
+    names
+}
+
+///Dependencies declared by the extension attribute of the fragment, without the ones from the registry
+pub fn declared_dependencies(fragment: &File) -> Result<Vec<ExtensionKind>, MergeError> {
+    parse_kinds(&find_list_arg(&extension_args(fragment), "dependencies"))
+}
+
+impl ExtensionDescriptor {
+    ///Dependencies and conflicts declared by the fragment are added to the ones from the registry
+    pub fn from_fragment(kind: &ExtensionKind, fragment: &File, standard: &StandardDescriptor) -> Result<ExtensionDescriptor, MergeError> {
+        let args = extension_args(fragment);
+
+        Ok(ExtensionDescriptor {
+            name: standard.extension_path(kind),
+            title: find_str_arg(&args, "title").unwrap_or_else(|| kind.to_string()),
+            description: find_str_arg(&args, "description"),
+            dependencies: merge_names(standard, standard.dependencies(kind), &declared_dependencies(fragment)?),
+            conflicts: merge_names(standard, standard.conflicts(kind), &parse_kinds(&find_list_arg(&args, "conflicts"))?),
+            files: target_files(fragment)?.iter().map(|file| file.to_string()).collect(),
+            constructor_params: constructor_params(fragment, kind)?,
+            excluded_constructors: storage_structs(fragment).flat_map(parse_excluded_constructors).collect(),
+            standards: find_list_arg(&args, "standards"),
+        })
+    }
+
+    ///Checks that the fragment supports the standard and everything it declares is a part of the selection
+    pub fn validate(&self, standard: &StandardDescriptor, selected: &[ExtensionKind]) -> Result<(), DependencyError> {
+        if !self.standards.is_empty() && !self.standards.contains(&standard.name) {
+            return Err(DependencyError::new(&format!("{} extension supports only {}", self.name, self.standards.join(", "))));
+        }
+        let selected = selected.iter().map(|kind| standard.extension_path(kind)).collect::<Vec<_>>();
+        if let Some(dependency) = self.dependencies.iter().find(|dependency| !selected.contains(dependency)) {
+            return Err(DependencyError::new(&format!("{} extension requires {}", self.name, dependency)));
+        }
+        if let Some(conflict) = self.conflicts.iter().find(|conflict| selected.contains(conflict)) {
+            return Err(DependencyError::new(&format!("{} can not be used together with {}", self.name, conflict)));
+        }
+        Ok(())
+    }
+}
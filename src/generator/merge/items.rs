@@ -0,0 +1,118 @@
+use syn::{Ident, Item, ItemMod, ItemTrait, parse_quote, TraitItem};
+use syn::__private::ToTokens;
+
+use crate::generator::ast::{find_attribute, strip_smart_beaver_attributes};
+use crate::generator::merge::conflicts::ConflictTracker;
+use crate::generator::merge::report::MergeReport;
+use crate::generator::source_parser::ExtensionKind;
+use crate::logger::console_log;
+use crate::logger::log;
+
+///Name and kind of items copied from extensions as they are - events, enums, consts, type aliases, free functions, etc.
+/// Uses, storage and impl blocks are merged separately
+fn describe_item(item: &Item) -> Option<(&'static str, &Ident)> {
+    match item {
+        Item::Struct(item_struct) => {
+            if find_attribute(&item_struct.attrs, &parse_quote!(#[smart_beaver::storage])).is_some()
+                || find_attribute(&item_struct.attrs, &parse_quote!(#[ink(storage)])).is_some() {
+                None
+            } else if find_attribute(&item_struct.attrs, &parse_quote!(#[ink(event)])).is_some() {
+                Some(("event", &item_struct.ident))
+            } else {
+                Some(("struct", &item_struct.ident))
+            }
+        }
+        Item::Enum(item_enum) => Some(("enum", &item_enum.ident)),
+        Item::Const(item_const) => Some(("const", &item_const.ident)),
+        Item::Static(item_static) => Some(("static", &item_static.ident)),
+        Item::Type(item_type) => Some(("type", &item_type.ident)),
+        Item::Fn(item_fn) => Some(("fn", &item_fn.sig.ident)),
+        Item::Trait(item_trait) => Some(("trait", &item_trait.ident)),
+        _ => None
+    }
+}
+
+fn strip_item_attributes(item: &Item) -> Item {
+    let mut item = item.clone();
+    match &mut item {
+        Item::Struct(item_struct) => item_struct.attrs = strip_smart_beaver_attributes(&item_struct.attrs),
+        Item::Enum(item_enum) => item_enum.attrs = strip_smart_beaver_attributes(&item_enum.attrs),
+        Item::Const(item_const) => item_const.attrs = strip_smart_beaver_attributes(&item_const.attrs),
+        Item::Static(item_static) => item_static.attrs = strip_smart_beaver_attributes(&item_static.attrs),
+        Item::Type(item_type) => item_type.attrs = strip_smart_beaver_attributes(&item_type.attrs),
+        Item::Fn(item_fn) => item_fn.attrs = strip_smart_beaver_attributes(&item_fn.attrs),
+        Item::Trait(item_trait) => item_trait.attrs = strip_smart_beaver_attributes(&item_trait.attrs),
+        _ => {}
+    }
+    item
+}
+
+fn trait_item_name(trait_item: &TraitItem) -> Option<&Ident> {
+    match trait_item {
+        TraitItem::Fn(trait_fn) => Some(&trait_fn.sig.ident),
+        TraitItem::Const(trait_const) => Some(&trait_const.ident),
+        TraitItem::Type(trait_type) => Some(&trait_type.ident),
+        _ => None
+    }
+}
+
+///Adds missing items (e.g. methods) to the trait already defined in the contract
+fn merge_trait(target_trait: &mut ItemTrait, extension_trait: &ItemTrait, conflicts: &mut ConflictTracker, report: &mut MergeReport, extension_kind: &ExtensionKind) {
+    for trait_item in &extension_trait.items {
+        let Some(ident) = trait_item_name(trait_item) else {
+            continue;
+        };
+        let name = format!("{}::{}", target_trait.ident, ident);
+        let existing = target_trait.items.iter().find(|target_item| trait_item_name(target_item) == Some(ident));
+
+        match existing {
+            Some(existing_item) if existing_item.to_token_stream().to_string() == trait_item.to_token_stream().to_string() => {
+                console_log!("Identical trait item {} already present, skipping", name);
+            }
+            Some(_) => conflicts.record_item("trait item", &name, extension_kind, true),
+            None => {
+                console_log!("Copying trait item: {}", name);
+                conflicts.record_item("trait item", &name, extension_kind, false);
+                report.extension_mut(extension_kind).items.push(format!("trait item {}", name));
+                target_trait.items.push(trait_item.clone());
+            }
+        }
+    }
+}
+
+///Copies events, enums, consts, type aliases and free functions from the extension into the base contract.
+/// New items are placed before the first impl block. Item identical to the one already present is skipped,
+/// trait with the same name is extended with missing items and any other item with the same name is reported as a conflict
+pub fn merge_items(base_contract: &mut ItemMod, extension: &ItemMod, conflicts: &mut ConflictTracker, report: &mut MergeReport, extension_kind: &ExtensionKind) {
+    let (Some((_, content)), Some((_, extension_content))) = (base_contract.content.as_mut(), extension.content.as_ref()) else {
+        console_log!("Base contract or extension does not have content");
+        return;
+    };
+
+    for extension_item in extension_content {
+        let Some((item_kind, ident)) = describe_item(extension_item) else {
+            continue;
+        };
+        let item = strip_item_attributes(extension_item);
+        let existing = content.iter_mut().find(|base_item| describe_item(base_item).map(|(_, base_ident)| base_ident) == Some(ident));
+
+        if let Some(existing_item) = existing {
+            match (existing_item, &item) {
+                (Item::Trait(target_trait), Item::Trait(extension_trait)) => {
+                    merge_trait(target_trait, extension_trait, conflicts, report, extension_kind);
+                }
+                (existing_item, item) if existing_item.to_token_stream().to_string() == item.to_token_stream().to_string() => {
+                    console_log!("Identical {} {} already present, skipping", item_kind, ident);
+                }
+                _ => conflicts.record_item(item_kind, &ident.to_string(), extension_kind, true),
+            }
+            continue;
+        }
+
+        console_log!("Copying {}: {}", item_kind, ident);
+        conflicts.record_item(item_kind, &ident.to_string(), extension_kind, false);
+        report.extension_mut(extension_kind).items.push(format!("{} {}", item_kind, ident));
+        let idx = content.iter().position(|base_item| matches!(base_item, Item::Impl(_))).unwrap_or(content.len());
+        content.insert(idx, item);
+    }
+}
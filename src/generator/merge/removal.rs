@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use syn::{Attribute, Expr, ExprField, ExprMethodCall, ImplItemFn, ItemMod, Macro, Member, parse_quote, Path, Token};
+use syn::punctuated::Punctuated;
+use syn::visit::Visit;
+
+use crate::generator::ast::{extract_attribute_expression, find_attribute, parse_expr_as_flag, parse_expr_as_name_list};
+use crate::generator::merge::errors::MergeError;
+use crate::generator::source_parser::ExtensionKind;
+
+///Element of the base contract removed by `#[smart_beaver::remove]`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RemovedItem {
+    Function { impl_block: String, name: String },
+    Param { function: String, name: String },
+    Field { name: String },
+    Impl { name: String },
+    Use { name: String },
+}
+
+impl fmt::Display for RemovedItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RemovedItem::Function { impl_block, name } => write!(f, "function {impl_block}::{name}"),
+            RemovedItem::Param { function, name } => write!(f, "param {name} of {function}"),
+            RemovedItem::Field { name } => write!(f, "storage field {name}"),
+            RemovedItem::Impl { name } => write!(f, "impl block {name}"),
+            RemovedItem::Use { name } => write!(f, "use {name}"),
+        }
+    }
+}
+
+pub fn has_remove_attribute(attributes: &[Attribute]) -> bool {
+    find_attribute(attributes, &parse_quote!(#[smart_beaver::remove])).is_some()
+}
+
+///Names of params listed in `#[smart_beaver::remove(params = [a, b])]`.
+/// Returns `None` when entire item is removed
+pub fn parse_removed_params(attributes: &[Attribute]) -> Option<Vec<String>> {
+    extract_attribute_expression(attributes, &parse_quote!(#[smart_beaver::remove]))
+        .and_then(|expr| match expr {
+            Expr::Assign(assign) if parse_expr_as_flag(&assign.left).as_deref() == Some("params") => parse_expr_as_name_list(&assign.right),
+            _ => None
+        })
+}
+
+///Identifiers referenced by the code
+#[derive(Default)]
+struct References {
+    ///Methods and fields accessed on `self`, e.g. `self.transfer()` or `self.owner`
+    self_members: HashSet<String>,
+    ///Segments of all paths used in expressions and types
+    paths: Vec<Vec<String>>,
+    ///Identifiers used in macros which arguments are not a list of expressions
+    macro_idents: HashSet<String>,
+}
+
+fn is_self(expr: &Expr) -> bool {
+    matches!(expr, Expr::Path(path) if path.path.is_ident("self"))
+}
+
+fn collect_idents(tokens: &str, idents: &mut HashSet<String>) {
+    tokens.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| !token.is_empty())
+        .for_each(|token| {
+            idents.insert(token.to_owned());
+        });
+}
+
+impl<'ast> Visit<'ast> for References {
+    fn visit_expr_method_call(&mut self, method_call: &'ast ExprMethodCall) {
+        if is_self(&method_call.receiver) {
+            self.self_members.insert(method_call.method.to_string());
+        }
+        syn::visit::visit_expr_method_call(self, method_call);
+    }
+
+    fn visit_expr_field(&mut self, field: &'ast ExprField) {
+        if let (true, Member::Named(ident)) = (is_self(&field.base), &field.member) {
+            self.self_members.insert(ident.to_string());
+        }
+        syn::visit::visit_expr_field(self, field);
+    }
+
+    fn visit_path(&mut self, path: &'ast Path) {
+        self.paths.push(path.segments.iter().map(|segment| segment.ident.to_string()).collect());
+        syn::visit::visit_path(self, path);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        //Most macros (assert!, assert_eq!, ...) take expressions, so they can be checked as regular code
+        match mac.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated) {
+            Ok(args) => args.iter().for_each(|arg| self.visit_expr(arg)),
+            Err(_) => collect_idents(&mac.tokens.to_string(), &mut self.macro_idents),
+        }
+        syn::visit::visit_macro(self, mac);
+    }
+}
+
+impl References {
+    fn of_mod(root_mod: &ItemMod) -> References {
+        let mut references = References::default();
+        references.visit_item_mod(root_mod);
+        references
+    }
+
+    fn of_fn(impl_fn: &ImplItemFn) -> References {
+        let mut references = References::default();
+        references.visit_block(&impl_fn.block);
+        references
+    }
+
+    fn contains_path(&self, predicate: impl Fn(&[String]) -> bool) -> bool {
+        self.paths.iter().any(|segments| predicate(segments))
+    }
+
+    fn is_referenced(&self, item: &RemovedItem) -> bool {
+        match item {
+            RemovedItem::Function { impl_block, name } => {
+                self.self_members.contains(name) || self.macro_idents.contains(name) || self.contains_path(|segments| {
+                    //`Self::name` or `ImplName::name`
+                    segments.len() >= 2
+                        && &segments[segments.len() - 1] == name
+                        && (segments[segments.len() - 2] == "Self" || &segments[segments.len() - 2] == impl_block)
+                })
+            }
+            RemovedItem::Field { name } => self.self_members.contains(name) || self.macro_idents.contains(name),
+            RemovedItem::Param { name, .. } => {
+                self.macro_idents.contains(name) || self.contains_path(|segments| segments.len() == 1 && &segments[0] == name)
+            }
+            RemovedItem::Impl { name } | RemovedItem::Use { name } => {
+                self.macro_idents.contains(name) || self.contains_path(|segments| segments.contains(name))
+            }
+        }
+    }
+}
+
+fn find_fn<'a>(root_mod: &'a ItemMod, name: &str) -> Option<&'a ImplItemFn> {
+    root_mod.content.as_ref()?.1.iter().find_map(|item| match item {
+        syn::Item::Impl(impl_block) => impl_block.items.iter().find_map(|impl_item| match impl_item {
+            syn::ImplItem::Fn(impl_fn) if impl_fn.sig.ident == name => Some(impl_fn),
+            _ => None
+        }),
+        _ => None
+    })
+}
+
+///Makes sure that items removed by extensions are not used by the merged code.
+/// Params are checked only inside the function they were removed from
+pub fn validate_removals(root_mod: &ItemMod, removed: &[(ExtensionKind, RemovedItem)]) -> Result<(), MergeError> {
+    let mod_references = References::of_mod(root_mod);
+
+    for (extension, item) in removed {
+        let referenced = match item {
+            RemovedItem::Param { function, .. } => find_fn(root_mod, function)
+                .map(|impl_fn| References::of_fn(impl_fn).is_referenced(item))
+                .unwrap_or(false),
+            _ => mod_references.is_referenced(item),
+        };
+
+        if referenced {
+            return Err(MergeError::new(&format!("{} removed by {} is still referenced", item, extension.path())));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use serde::Serialize;
+
+use crate::generator::source_parser::ExtensionKind;
+
+///Changes made to the base contract by a single extension
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ExtensionReport {
+    pub extension: String,
+    ///Output files changed by the extension, e.g. "lib.rs" or "traits.rs"
+    pub files: Vec<String>,
+    pub storage_fields: Vec<String>,
+    pub constructor_params: Vec<String>,
+    ///Fields initialised with values supplied by the caller
+    pub constructor_values: Vec<String>,
+    pub functions_copied: Vec<String>,
+    pub functions_appended: Vec<String>,
+    pub functions_replaced: Vec<String>,
+    pub functions_guarded: Vec<String>,
+    pub impl_blocks: Vec<String>,
+    ///Events, enums, consts and other module level items, e.g. "event Paused"
+    pub items: Vec<String>,
+    ///Variants added to the standard's error enum, e.g. "PSP22Error::CapExceeded"
+    pub error_variants: Vec<String>,
+    pub uses: Vec<String>,
+    pub removed: Vec<String>,
+    ///Problems which did not stop the merge, e.g. function skipped because its target was not found
+    pub warnings: Vec<String>,
+}
+
+///Summary of everything the merger did, grouped by extension in merge order
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MergeReport {
+    pub extensions: Vec<ExtensionReport>,
+}
+
+impl MergeReport {
+    ///Report entry of given extension. Entry is created when extension is seen for the first time
+    pub fn extension_mut(&mut self, kind: &ExtensionKind) -> &mut ExtensionReport {
+        let name = kind.path();
+        let idx = match self.extensions.iter().position(|report| report.extension == name) {
+            Some(idx) => idx,
+            None => {
+                self.extensions.push(ExtensionReport { extension: name, ..Default::default() });
+                self.extensions.len() - 1
+            }
+        };
+        &mut self.extensions[idx]
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Merge report is always serializable")
+    }
+}
+
+///Adds value to the report list, skipping duplicates (e.g. the same param added to multiple constructors)
+pub fn push_unique(list: &mut Vec<String>, value: String) {
+    if !list.contains(&value) {
+        list.push(value);
+    }
+}
@@ -59,6 +59,21 @@ pub fn has_use(items: &[Item], compare_use: ItemUse) -> Result<bool, ComparisonE
     }
 }
 
+fn tree_imports_name(tree: &UseTree, name: &str) -> bool {
+    match tree {
+        UseTree::Path(path) => tree_imports_name(path.tree.as_ref(), name),
+        UseTree::Name(use_name) => use_name.ident == name,
+        UseTree::Rename(rename) => rename.rename == name,
+        UseTree::Glob(_) => false,
+        UseTree::Group(group) => group.items.iter().any(|item| tree_imports_name(item, name)),
+    }
+}
+
+///Checks whether some use statement brings `name` into scope, whatever path it is imported from
+pub fn imports_name(items: &[Item], name: &str) -> bool {
+    extract_uses(items).iter().any(|item_use| tree_imports_name(&item_use.tree, name))
+}
+
 pub fn extract_uses(items: &[Item]) -> Vec<&ItemUse> {
     items.iter().filter_map(|i| {
         if let Item::Use(use_block) = i {
@@ -67,3 +82,52 @@ pub fn extract_uses(items: &[Item]) -> Vec<&ItemUse> {
         None
     }).collect::<Vec<&ItemUse>>()
 }
+
+fn filter_use_tree(tree: &UseTree, prefix: &str, removed: &[String], removed_names: &mut Vec<String>) -> Option<UseTree> {
+    match tree {
+        UseTree::Path(path) => {
+            let name = path.ident.to_string();
+            filter_use_tree(path.tree.as_ref(), &format!("{prefix}::{name}"), removed, removed_names).map(|subtree| {
+                let mut path = path.clone();
+                path.tree = Box::new(subtree);
+                UseTree::Path(path)
+            })
+        }
+        UseTree::Name(name) => {
+            if removed.contains(&format!("{prefix}::{}", name.ident)) {
+                removed_names.push(name.ident.to_string());
+                None
+            } else {
+                Some(tree.clone())
+            }
+        }
+        UseTree::Group(group) => {
+            let mut group = group.clone();
+            group.items = group.items.iter()
+                .filter_map(|item| filter_use_tree(item, prefix, removed, removed_names))
+                .collect();
+            (!group.items.is_empty()).then_some(UseTree::Group(group))
+        }
+        UseTree::Rename(_) | UseTree::Glob(_) => Some(tree.clone()),
+    }
+}
+
+///Removes names imported by `removed_use` from all use statements.
+/// Grouped imports are narrowed down, use statements left without any name are dropped.
+/// Returns names which were removed
+pub fn remove_use(items: &mut Vec<Item>, removed_use: &ItemUse) -> Result<Vec<String>, ComparisonError> {
+    let removed = get_last_path_segments(&removed_use.tree, "_")?;
+    let mut removed_names = Vec::new();
+
+    items.retain_mut(|item| {
+        if let Item::Use(item_use) = item {
+            match filter_use_tree(&item_use.tree, "_", &removed, &mut removed_names) {
+                None => return false,
+                Some(tree) => item_use.tree = tree,
+            }
+        }
+        true
+    });
+
+    Ok(removed_names)
+}
@@ -2,6 +2,8 @@ pub mod merge;
 pub mod source_parser;
 mod ast;
 pub mod manifest_parser;
+pub mod dependencies;
+pub mod registry;
 
 pub use source_parser::CONTRACT_EXTENSION_FILE_TYPE;
 pub use source_parser::BASE_CONTRACT_FILE_TYPE;
\ No newline at end of file
@@ -0,0 +1,264 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::{ExternalCrate, get_all_files, OutputFile, Standard};
+use crate::code_loader::loader::{is_not_found, SourceLoader};
+use crate::generator::dependencies::{conflicts, dependencies, supported_extensions};
+use crate::generator::source_parser::ExtensionKind;
+use crate::logger::console_log;
+use crate::logger::log;
+
+///Manifest published next to the templates, declaring the standards available at the source
+pub const REGISTRY_MANIFEST_FILE: &str = "standards.toml";
+
+#[derive(Debug)]
+pub struct RegistryError(String);
+
+impl RegistryError {
+    pub fn new(message: &str) -> RegistryError {
+        RegistryError(message.to_string())
+    }
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid standards registry: {}", self.0)
+    }
+}
+
+impl Error for RegistryError {}
+
+///Extension available for the standard
+#[derive(Clone, Debug, PartialEq)]
+pub struct SupportedExtension {
+    pub kind: ExtensionKind,
+    ///Directory of the fragment inside `extensions`, e.g. `security` for ownable
+    pub prefix: Option<String>,
+    pub dependencies: Vec<ExtensionKind>,
+    pub conflicts: Vec<ExtensionKind>,
+}
+
+impl SupportedExtension {
+    ///Path of the fragment relative to the `extensions` directory, without file type
+    pub fn path(&self) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}/{}", prefix, self.kind.file_name()),
+            None => self.kind.file_name(),
+        }
+    }
+}
+
+///Everything the generator needs to know about a standard besides its templates
+#[derive(Clone, Debug, PartialEq)]
+pub struct StandardDescriptor {
+    ///Name of the standard, also the directory with its templates
+    pub name: String,
+    ///Built-in standard whose merge rules (e.g. metadata baked into the constructor) are applied
+    pub rules: Option<Standard>,
+    pub external_crate: Option<ExternalCrate>,
+    pub extensions: Vec<SupportedExtension>,
+    ///Files generated for the standard
+    pub files: Vec<OutputFile>,
+    ///Only the contract mod is kept in single file mode, everything around it is provided by the external crate
+    pub contract_mod_only: bool,
+}
+
+impl StandardDescriptor {
+    pub fn builtin(standard: Standard) -> StandardDescriptor {
+        let extensions = supported_extensions(standard).iter()
+            .map(|kind| SupportedExtension {
+                kind: kind.clone(),
+                prefix: match kind {
+                    ExtensionKind::Ownable | ExtensionKind::AccessControl => Some("security".to_owned()),
+                    _ => None,
+                },
+                dependencies: dependencies(standard, kind).to_vec(),
+                conflicts: conflicts(standard, kind).to_vec(),
+            })
+            .collect();
+
+        StandardDescriptor {
+            name: standard.to_string(),
+            rules: Some(standard),
+            external_crate: standard.get_external_crate_name(),
+            extensions,
+            files: get_all_files(),
+            contract_mod_only: standard != Standard::PSP34,
+        }
+    }
+
+    pub fn extension(&self, kind: &ExtensionKind) -> Option<&SupportedExtension> {
+        self.extensions.iter().find(|extension| &extension.kind == kind)
+    }
+
+    pub fn dependencies(&self, kind: &ExtensionKind) -> &[ExtensionKind] {
+        self.extension(kind).map(|extension| extension.dependencies.as_slice()).unwrap_or_default()
+    }
+
+    pub fn conflicts(&self, kind: &ExtensionKind) -> &[ExtensionKind] {
+        self.extension(kind).map(|extension| extension.conflicts.as_slice()).unwrap_or_default()
+    }
+
+    ///Path of the extension fragment relative to the `extensions` directory, without file type
+    pub fn extension_path(&self, kind: &ExtensionKind) -> String {
+        self.extension(kind).map(SupportedExtension::path).unwrap_or_else(|| kind.path())
+    }
+}
+
+///Fields omitted in the manifest are taken from the built-in standard of the same rules
+#[derive(Deserialize)]
+struct ManifestExtension {
+    name: String,
+    #[serde(default)]
+    prefix: Option<String>,
+    #[serde(default)]
+    dependencies: Option<Vec<String>>,
+    #[serde(default)]
+    conflicts: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct ManifestStandard {
+    name: String,
+    #[serde(default)]
+    rules: Option<String>,
+    #[serde(default)]
+    external_crate: Option<ExternalCrate>,
+    #[serde(default)]
+    extensions: Vec<ManifestExtension>,
+    #[serde(default)]
+    files: Option<Vec<String>>,
+    #[serde(default)]
+    contract_mod_only: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    standards: Vec<ManifestStandard>,
+}
+
+fn parse_extension_kind(name: &str, standard: &str) -> Result<ExtensionKind, RegistryError> {
+    ExtensionKind::from_str(name).map_err(|e| RegistryError::new(&format!("{} of {}", e, standard)))
+}
+
+fn parse_extension_kinds(names: &[String], standard: &str) -> Result<Vec<ExtensionKind>, RegistryError> {
+    names.iter().map(|name| parse_extension_kind(name, standard)).collect()
+}
+
+impl ManifestExtension {
+    fn into_supported(self, standard: &str, builtin: Option<&StandardDescriptor>) -> Result<SupportedExtension, RegistryError> {
+        let kind = parse_extension_kind(&self.name, standard)?;
+        let builtin = builtin.and_then(|builtin| builtin.extension(&kind));
+        let kinds = |names: Option<Vec<String>>, default: Option<&Vec<ExtensionKind>>| match names {
+            Some(names) => parse_extension_kinds(&names, standard),
+            None => Ok(default.cloned().unwrap_or_default()),
+        };
+
+        Ok(SupportedExtension {
+            prefix: self.prefix.or_else(|| builtin.and_then(|builtin| builtin.prefix.clone())),
+            dependencies: kinds(self.dependencies, builtin.map(|builtin| &builtin.dependencies))?,
+            conflicts: kinds(self.conflicts, builtin.map(|builtin| &builtin.conflicts))?,
+            kind,
+        })
+    }
+}
+
+impl ManifestStandard {
+    fn into_descriptor(self) -> Result<StandardDescriptor, RegistryError> {
+        let name = self.name;
+        let rules = match self.rules {
+            Some(rules) => Some(Standard::from_str(&rules).map_err(|_| RegistryError::new(&format!("Unknown rules {} of {}", rules, name)))?),
+            None => Standard::from_str(&name).ok(),
+        };
+        let builtin = rules.map(StandardDescriptor::builtin);
+        let extensions = self.extensions.into_iter()
+            .map(|extension| extension.into_supported(&name, builtin.as_ref()))
+            .collect::<Result<Vec<_>, RegistryError>>()?;
+        let files = match self.files {
+            Some(files) => files.iter()
+                .map(|file| OutputFile::from_str(file).map_err(|_| RegistryError::new(&format!("Unknown file {} of {}", file, name))))
+                .collect::<Result<Vec<_>, RegistryError>>()?,
+            None => builtin.as_ref().map(|builtin| builtin.files.clone()).unwrap_or_else(get_all_files),
+        };
+        let contract_mod_only = self.contract_mod_only
+            .or_else(|| builtin.as_ref().map(|builtin| builtin.contract_mod_only))
+            .unwrap_or(false);
+
+        Ok(StandardDescriptor { name, rules, external_crate: self.external_crate, extensions, files, contract_mod_only })
+    }
+}
+
+///Standards known to the generator
+#[derive(Clone, Debug)]
+pub struct Registry {
+    standards: Vec<StandardDescriptor>,
+}
+
+impl Registry {
+    pub fn builtin() -> Registry {
+        Registry {
+            standards: vec![Standard::PSP22, Standard::PSP34, Standard::PSP37].into_iter().map(StandardDescriptor::builtin).collect(),
+        }
+    }
+
+    ///Built-in standards extended with the ones declared in the TOML or JSON manifest.
+    /// Standard declared in the manifest replaces the built-in one with the same name
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ink_generator::generator::registry::Registry;
+    ///
+    /// let registry = Registry::parse(r#"
+    /// [[standards]]
+    /// name = "PSP22"
+    /// external_crate = { name = "psp22", version = "1.0.0" }
+    /// extensions = [{ name = "ownable", prefix = "access" }]
+    /// "#).unwrap();
+    /// let psp22 = registry.standard("PSP22").unwrap();
+    /// assert_eq!(psp22.external_crate.as_ref().unwrap().name, "psp22");
+    /// assert_eq!(psp22.extensions[0].path(), "access/ownable");
+    /// assert!(registry.standard("PSP34").is_ok());
+    /// ```
+    pub fn parse(manifest: &str) -> Result<Registry, RegistryError> {
+        let manifest: Manifest = match manifest.trim_start().starts_with('{') {
+            true => serde_json::from_str(manifest).map_err(|e| RegistryError::new(&e.to_string()))?,
+            false => toml::from_str(manifest).map_err(|e| RegistryError::new(&e.to_string()))?,
+        };
+
+        let mut registry = Registry::builtin();
+        for standard in manifest.standards {
+            let descriptor = standard.into_descriptor()?;
+            registry.standards.retain(|existing| existing.name != descriptor.name);
+            registry.standards.push(descriptor);
+        }
+        Ok(registry)
+    }
+
+    pub fn standards(&self) -> &[StandardDescriptor] {
+        &self.standards
+    }
+
+    pub fn standard(&self, name: &str) -> Result<&StandardDescriptor, RegistryError> {
+        self.standards.iter()
+            .find(|standard| standard.name == name)
+            .ok_or_else(|| RegistryError::new(&format!("Unknown standard: {}", name)))
+    }
+}
+
+///Loads the registry manifest from the source. Built-in standards are used when the source does not publish one,
+/// any other failure (e.g. the source is unreachable) is reported, so the built-in standards are not used by mistake
+pub async fn load_registry(loader: &dyn SourceLoader) -> Result<Registry, RegistryError> {
+    match loader.load(REGISTRY_MANIFEST_FILE).await {
+        Ok(manifest) => Registry::parse(&manifest),
+        Err(error) if is_not_found(error.as_ref()) => {
+            console_log!("Standards registry not found, using built-in standards");
+            Ok(Registry::builtin())
+        }
+        Err(error) => Err(RegistryError::new(&format!("{} could not be loaded: {}", REGISTRY_MANIFEST_FILE, error))),
+    }
+}
@@ -1,27 +1,49 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::str::FromStr;
-use enum_map::Enum;
 
 use futures::future::join_all;
 
-use crate::{Contract, prettifier};
-use crate::code_loader::loader::load_source;
-use crate::generator::merge::{AstMerger, Merger};
+use crate::{OutputFile, prettifier};
+use crate::code_loader::loader::{is_not_found, SourceLoader};
+use crate::generator::dependencies::{InclusionReason, resolve_extensions, ResolvedExtension};
+use crate::generator::merge::{AstMerger, ContractFiles, Merger, target_files};
+use crate::generator::merge::conflicts::MergeConflict;
+use crate::generator::merge::constructor_params::ConstructorSignature;
+use crate::generator::merge::error_variants::ErrorVariants;
+use crate::generator::merge::extension_descriptor::{declared_dependencies, ExtensionDescriptor};
+use crate::generator::merge::report::MergeReport;
+use crate::generator::registry::{load_registry, StandardDescriptor};
 use crate::logger::console_log;
+use crate::project::GenerationRequest;
 use crate::logger::log;
 
 pub const BASE_CONTRACT_FILE_TYPE: &str = ".rs";
 
 pub const CONTRACT_EXTENSION_FILE_TYPE: &str = ".trs";
 
+pub struct ParserOutput {
+    ///Generated code of every merged file
+    pub files: HashMap<OutputFile, String>,
+    pub extensions: Vec<ResolvedExtension>,
+    pub conflicts: Vec<MergeConflict>,
+    pub report: MergeReport,
+    ///Error variants to be merged into the errors file
+    pub error_variants: Vec<ErrorVariants>,
+    pub constructors: Vec<ConstructorSignature>,
+    ///Standard resolved with the registry of the source
+    pub standard: StandardDescriptor,
+}
+
 #[derive(Clone)]
 pub struct ExtensionContext {
     pub kind: ExtensionKind,
     pub ast: syn::File,
 }
 
-#[derive(Clone, Debug, Copy, Enum)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ExtensionKind {
     Metadata,
     Mintable,
@@ -34,14 +56,30 @@ pub enum ExtensionKind {
     Enumerable,
     Ownable,
     AccessControl,
+    ///Third-party extension, loaded from `{name}.trs` and merged with the same rules as the built-in ones.
+    /// Selected with the `custom:` prefix, e.g. `custom:finance/vesting`
+    Custom(String),
 }
 
 impl fmt::Display for ExtensionKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", format!("{:?}", self).to_lowercase())
+        match self {
+            ExtensionKind::Custom(name) => write!(f, "{}", name),
+            _ => write!(f, "{}", format!("{:?}", self).to_lowercase()),
+        }
     }
 }
 
+///Prefix opting in to a custom extension, so a misspelled built-in name is not taken for one
+pub const CUSTOM_EXTENSION_PREFIX: &str = "custom:";
+
+///Custom extension name is a path of the fragment, e.g. `vesting` or `finance/vesting`
+fn is_valid_custom_name(name: &str) -> bool {
+    !name.is_empty() && name.split('/').all(|segment| {
+        !segment.is_empty() && segment.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    })
+}
+
 impl FromStr for ExtensionKind {
     type Err = Box<dyn Error>;
 
@@ -56,59 +94,199 @@ impl FromStr for ExtensionKind {
             "capped" => Ok(ExtensionKind::Capped),
             "batch" => Ok(ExtensionKind::Batch),
             "enumerable" => Ok(ExtensionKind::Enumerable),
-            "security/ownable" => Ok(ExtensionKind::Ownable),
-            "security/access_control" => Ok(ExtensionKind::AccessControl),
-            _ => Err(format!("Unknown extension: {}", s).into()),
+            "security/ownable" | "ownable" => Ok(ExtensionKind::Ownable),
+            "security/access_control" | "access_control" => Ok(ExtensionKind::AccessControl),
+            _ => match s.strip_prefix(CUSTOM_EXTENSION_PREFIX) {
+                Some(custom) if is_valid_custom_name(custom) => Ok(ExtensionKind::Custom(custom.to_owned())),
+                _ => Err(format!("Unknown extension: {}", s).into()),
+            },
+        }
+    }
+}
+
+impl ExtensionKind {
+    ///Path of the extension fragment relative to the `extensions` directory, without file type
+    /// Security related extensions are located in `security/` directory
+    pub fn path(&self) -> String {
+        match self {
+            ExtensionKind::Ownable | ExtensionKind::AccessControl => format!("security/{}", self.file_name()),
+            _ => self.file_name(),
+        }
+    }
+
+    ///Merge priority used to order extensions which do not depend on each other.
+    /// Lower value is merged first. Access control goes first, so every other extension can rely on it,
+    /// extensions that modify existing messages (e.g. capped, pausable) go last.
+    pub fn priority(&self) -> u8 {
+        match self {
+            ExtensionKind::Ownable => 0,
+            ExtensionKind::AccessControl => 1,
+            ExtensionKind::Metadata => 10,
+            ExtensionKind::Wrapper => 20,
+            ExtensionKind::Mintable => 30,
+            ExtensionKind::Burnable => 40,
+            ExtensionKind::FlashMint => 50,
+            ExtensionKind::Batch => 60,
+            ExtensionKind::Enumerable => 70,
+            ExtensionKind::Capped => 80,
+            ExtensionKind::Pausable => 90,
+            ExtensionKind::Custom(_) => 100,
+        }
+    }
+
+    pub(crate) fn file_name(&self) -> String {
+        match self {
+            ExtensionKind::FlashMint => "flash_mint".to_owned(),
+            ExtensionKind::AccessControl => "access_control".to_owned(),
+            _ => self.to_string(),
         }
     }
 }
 
 
-async fn load_parse_ast(filepath: &str) -> Result<syn::File, Box<dyn Error>> {
-    let code_string = load_source(filepath).await?;
+async fn load_parse_ast(loader: &dyn SourceLoader, filepath: &str) -> Result<syn::File, Box<dyn Error>> {
+    let code_string = loader.load(filepath).await?;
     Ok(syn::parse_str(&code_string)?)
 }
 
-async fn load_base_contract(source: &str, standard: &str) -> Result<syn::File, Box<dyn Error>> {
+async fn load_base_contract(loader: &dyn SourceLoader, standard: &str) -> Result<syn::File, Box<dyn Error>> {
     console_log!("Loading base contract[{standard}]: lib{BASE_CONTRACT_FILE_TYPE}");
-    load_parse_ast(&format!("{source}/{standard}/lib{BASE_CONTRACT_FILE_TYPE}")).await
+    load_parse_ast(loader, &format!("{standard}/lib{BASE_CONTRACT_FILE_TYPE}")).await
 }
 
-async fn load_extension(extension: &str, source: &str, standard: &str) -> Result<syn::File, Box<dyn Error>> {
-    console_log!("Loading extension[{standard}]: {extension}");
-    load_parse_ast(&format!("{source}/{standard}/extensions/{extension}{CONTRACT_EXTENSION_FILE_TYPE}")).await
+///Source is returned with the syntax tree, so parts not changed by extensions can be copied as they are
+async fn load_static_file(file: OutputFile, loader: &dyn SourceLoader, standard: &str) -> Result<(String, syn::File), Box<dyn Error>> {
+    console_log!("Loading file extended by extensions[{standard}]: {file}");
+    let code_string = loader.load(&format!("{standard}/{file}")).await?;
+    let ast = syn::parse_str(&code_string)?;
+    Ok((code_string, ast))
 }
 
-pub async fn run(contract: Contract) -> Result<String, Box<dyn Error>> {
-    console_log!("Running parser for contract: {:#?}", contract);
-    let standard = &contract.standard.to_string();
-    let source = &contract.source;
-    let base_contract_ast = load_base_contract(&contract.source, standard).await?;
-
-    let extensions = join_all(
-        contract.extensions.iter()
-            .map(|extension| (extension, source, standard))
-            .map(|(extension, source, standard)| async move {
-                (extension, load_extension(extension, source, standard).await)
-            })
-    ).await;
+async fn load_extension(extension: &ExtensionKind, loader: &dyn SourceLoader, standard: &StandardDescriptor) -> Result<syn::File, Box<dyn Error>> {
+    let extension_path = standard.extension_path(extension);
+    let standard = &standard.name;
+    console_log!("Loading extension[{standard}]: {extension_path}");
+    load_parse_ast(loader, &format!("{standard}/extensions/{extension_path}{CONTRACT_EXTENSION_FILE_TYPE}")).await
+}
+
+///Resolves the selection and loads fragments of every resolved extension.
+/// Dependencies declared only by fragments are known once the fragments are loaded, so they are resolved in rounds
+async fn load_extensions(requested: &[ExtensionKind], loader: &dyn SourceLoader, descriptor: &StandardDescriptor) -> Result<(Vec<ResolvedExtension>, Vec<ExtensionContext>), Box<dyn Error>> {
+    let mut selection = requested.to_vec();
+    let mut declared_by: Vec<(ExtensionKind, ExtensionKind)> = Vec::new();
+    let mut extensions: Vec<ExtensionContext> = Vec::new();
 
-    //Ignore not loaded extensions
+    loop {
+        let resolved = resolve_extensions(descriptor, &selection)?;
+        let pending = resolved.iter()
+            .filter(|resolved| !extensions.iter().any(|extension| extension.kind == resolved.kind))
+            .map(|resolved| resolved.kind.clone())
+            .collect::<Vec<_>>();
+        let loaded = join_all(pending.iter().map(|kind| load_extension(kind, loader, descriptor))).await;
+        let mut added = false;
+        for (kind, load_result) in pending.into_iter().zip(loaded) {
+            let ast = load_result?;
+            for dependency in declared_dependencies(&ast)? {
+                if !resolved.iter().any(|resolved| resolved.kind == dependency) && !selection.contains(&dependency) {
+                    declared_by.push((dependency.clone(), kind.clone()));
+                    selection.push(dependency);
+                    added = true;
+                }
+            }
+            extensions.push(ExtensionContext { kind, ast });
+        }
+
+        if !added {
+            let resolved = resolved.into_iter().map(|mut extension| {
+                if let Some((_, by)) = declared_by.iter().find(|(dependency, _)| *dependency == extension.kind) {
+                    extension.reason = InclusionReason::RequiredBy(by.clone());
+                }
+                extension
+            }).collect::<Vec<_>>();
+            extensions.sort_by_key(|extension| resolved.iter().position(|resolved| resolved.kind == extension.kind));
+            return Ok((resolved, extensions));
+        }
+    }
+}
+
+pub async fn run(request: &GenerationRequest, loader: &dyn SourceLoader) -> Result<ParserOutput, Box<dyn Error>> {
+    console_log!("Running parser for contract: {:#?}", request);
+    let registry = load_registry(loader).await?;
+    let descriptor = registry.standard(&request.standard)?;
+    let standard = &descriptor.name;
+
+    let requested = request.extensions.iter()
+        .map(|extension| ExtensionKind::from_str(extension))
+        .collect::<Result<Vec<ExtensionKind>, Box<dyn Error>>>()?;
+    let base_contract_ast = load_base_contract(loader, standard).await?;
+    let (resolved_extensions, extensions) = load_extensions(&requested, loader, descriptor).await?;
+
+    let selected = resolved_extensions.iter().map(|extension| extension.kind.clone()).collect::<Vec<_>>();
     let mut extensions_checked: Vec<ExtensionContext> = Vec::new();
-    for (extension_name, load_result) in extensions {
-        extensions_checked.push(ExtensionContext {
-            kind: ExtensionKind::from_str(extension_name)?,
-            ast: load_result?,
-        });
+    for extension in extensions {
+        ExtensionDescriptor::from_fragment(&extension.kind, &extension.ast, descriptor)?.validate(descriptor, &selected)?;
+        extensions_checked.push(extension);
     }
 
-    let merger = Merger::merge(
-        &base_contract_ast,
+    //Files other than the contract are downloaded only when some extension changes them
+    let mut base_files = ContractFiles::from([(OutputFile::Main, base_contract_ast)]);
+    let mut static_sources = HashMap::new();
+    if !request.use_external_crate {
+        for extension in &extensions_checked {
+            for file in target_files(&extension.ast)? {
+                if let Entry::Vacant(entry) = base_files.entry(file) {
+                    let (source, ast) = load_static_file(file, loader, standard).await?;
+                    entry.insert(ast);
+                    static_sources.insert(file, source);
+                }
+            }
+        }
+    }
+
+    let merged = Merger::merge(
+        &base_files,
         extensions_checked,
-        contract.standard,
-        &contract.metadata,
-        contract.use_external_crate,
+        descriptor,
+        &request.metadata,
+        &request.constructor_values,
+        request.use_external_crate,
+        request.conflict_policy,
     )?;
 
-    Ok(prettifier::unparse(&merger))
+    Ok(ParserOutput {
+        files: merged.files.iter().map(|(file, ast)| match static_sources.get(file) {
+            Some(source) => (*file, prettifier::unparse_preserving(source, ast)),
+            None => (*file, prettifier::unparse(ast)),
+        }).collect(),
+        extensions: resolved_extensions,
+        conflicts: merged.conflicts,
+        report: merged.report,
+        error_variants: merged.error_variants,
+        constructors: merged.constructors,
+        standard: descriptor.clone(),
+    })
+}
+
+///Describes every extension available for the standard, so the extension picker can be rendered from data.
+/// Extensions listed in the registry without a published fragment are left out
+pub async fn describe_extensions(loader: &dyn SourceLoader, standard: &str) -> Result<Vec<ExtensionDescriptor>, Box<dyn Error>> {
+    let registry = load_registry(loader).await?;
+    let descriptor = registry.standard(standard)?;
+
+    let fragments = join_all(
+        descriptor.extensions.iter()
+            .map(|extension| async move {
+                (extension.kind.clone(), load_extension(&extension.kind, loader, descriptor).await)
+            })
+    ).await;
+
+    let mut extensions = Vec::new();
+    for (kind, load_result) in fragments {
+        match load_result {
+            Ok(fragment) => extensions.push(ExtensionDescriptor::from_fragment(&kind, &fragment, descriptor)?),
+            Err(error) if is_not_found(error.as_ref()) => console_log!("Skipping extension[{standard}]: {error}"),
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(extensions)
 }
@@ -1,19 +1,29 @@
 extern crate console_error_panic_hook;
 
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::panic;
 use std::str::FromStr;
 
 use wasm_bindgen::prelude::*;
 
-use generator::source_parser::run;
+use generator::dependencies::{InclusionReason, ResolvedExtension};
+use generator::merge::conflicts::{ConflictTarget, MergeConflict};
+use generator::merge::constructor_params::ConstructorSignature;
+use generator::merge::constructor_values::ConstructorValues;
+use generator::merge::errors::ConstructorValueError;
+use generator::merge::report::MergeReport;
+use generator::source_parser::describe_extensions;
+use project::{generate, GenerateError, GeneratedProject, GenerationRequest};
 
-use crate::code_loader::static_files::with_static_content;
+use crate::code_loader::cache_proxy::{CacheBackend, CacheConfig, clear_cache as clear_cached_files, EXPIRATION_TIME_MILISECONDS};
+use crate::code_loader::loader::default_loader;
 
 mod logger;
-mod code_loader;
+pub mod code_loader;
 pub mod generator;
 pub mod prettifier;
+pub mod project;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[wasm_bindgen]
@@ -58,6 +68,7 @@ impl Display for OutputFile {
 pub enum Standard {
     PSP22,
     PSP34,
+    PSP37,
 }
 
 impl Display for Standard {
@@ -65,25 +76,31 @@ impl Display for Standard {
         match self {
             Standard::PSP22 => "PSP22".fmt(f),
             Standard::PSP34 => "PSP34".fmt(f),
+            Standard::PSP37 => "PSP37".fmt(f),
         }
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
 pub struct ExternalCrate {
-    pub name: &'static str,
-    pub version: &'static str,
+    pub name: String,
+    pub version: String,
 }
 
 impl Standard {
     pub fn get_external_crate_name(&self) -> Option<ExternalCrate> {
         match self {
             Standard::PSP22 => Some(ExternalCrate {
-                name: "psp22-full",
-                version: "0.3.0",
+                name: "psp22-full".to_owned(),
+                version: "0.3.0".to_owned(),
             }),
             Standard::PSP34 => Some(ExternalCrate {
-                name: "psp34-full",
-                version: "0.2.1",
+                name: "psp34-full".to_owned(),
+                version: "0.2.1".to_owned(),
+            }),
+            Standard::PSP37 => Some(ExternalCrate {
+                name: "psp37-full".to_owned(),
+                version: "0.1.0".to_owned(),
             })
         }
     }
@@ -96,15 +113,28 @@ impl FromStr for Standard {
         match s {
             "PSP22" => Ok(Standard::PSP22),
             "PSP34" => Ok(Standard::PSP34),
+            "PSP37" => Ok(Standard::PSP37),
             _ => Err(()),
         }
     }
 }
 
+///Decides what happens when extensions contest the same element of the contract
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[wasm_bindgen]
+pub enum ConflictPolicy {
+    ///Merge fails and conflicts are returned as an error
+    Fail,
+    ///Merge succeeds and conflicts are returned as warnings
+    Warn,
+}
+
 #[derive(Debug)]
 #[wasm_bindgen]
 pub struct Contract {
-    pub standard: Standard,
+    ///Name of the standard, resolved with the standards registry of the source
+    #[wasm_bindgen(skip)]
+    pub standard: String,
 
     #[wasm_bindgen(skip)]
     pub metadata: Option<Metadata>,
@@ -118,13 +148,35 @@ pub struct Contract {
     #[wasm_bindgen(skip)]
     pub source: String,
 
+    ///Templates passed inline, keyed by path relative to the templates root, e.g. `PSP22/extensions/mintable.trs`.
+    /// When not empty nothing is downloaded from `source`
+    #[wasm_bindgen(skip)]
+    pub sources: HashMap<String, String>,
+
     #[wasm_bindgen(skip)]
     pub license_name: String,
 
     pub use_external_crate: bool,
+
+    pub conflict_policy: ConflictPolicy,
+
+    ///Values of extension storage fields baked into constructors, keyed by field name
+    #[wasm_bindgen(skip)]
+    pub constructor_values: ConstructorValues,
+
+    pub cache_enabled: bool,
+
+    pub cache_backend: CacheBackend,
+
+    ///Time in milliseconds after which cached templates are downloaded again, they never expire when not set
+    pub cache_ttl: Option<f64>,
+
+    ///Version of the template set, files cached for other versions are not used
+    #[wasm_bindgen(skip)]
+    pub cache_version: Option<String>,
 }
 
-fn get_all_files() -> Vec<OutputFile> {
+pub(crate) fn get_all_files() -> Vec<OutputFile> {
     Vec::from([
         OutputFile::Main,
         OutputFile::Data,
@@ -173,15 +225,27 @@ impl Metadata {
     }
 }
 
+///Plain JS object converted through its JSON form. `null` and `undefined` give the default value
+fn from_js_object<T: serde::de::DeserializeOwned + Default>(value: &JsValue) -> Result<T, String> {
+    if value.is_null() || value.is_undefined() {
+        return Ok(T::default());
+    }
+    let json = js_sys::JSON::stringify(value).ok()
+        .and_then(|json| json.as_string())
+        .ok_or_else(|| "value can not be converted to JSON".to_owned())?;
+    serde_json::from_str(&json).map_err(|error| error.to_string())
+}
+
 #[wasm_bindgen]
 impl Contract {
     #[wasm_bindgen(constructor)]
     pub fn new(standard: String, metadata: Option<Metadata>, extensions: js_sys::Array, source: String, license_name: String, use_external_crate: bool, files: Option<js_sys::Array>) -> Contract {
         Self {
-            standard: Standard::from_str(&standard).unwrap(),
+            standard,
             metadata,
             extensions: extensions.iter().map(|v| v.as_string().unwrap()).collect(),
             source,
+            sources: HashMap::new(),
             license_name,
             files: match files {
                 Some(unwrapped) => unwrapped.iter().map(|v| {
@@ -191,9 +255,25 @@ impl Contract {
                 None => get_all_files(),
             },
             use_external_crate,
+            conflict_policy: ConflictPolicy::Fail,
+            constructor_values: ConstructorValues::new(),
+            cache_enabled: true,
+            cache_backend: CacheBackend::LocalStorage,
+            cache_ttl: Some(EXPIRATION_TIME_MILISECONDS),
+            cache_version: None,
         }
     }
 
+    #[wasm_bindgen(getter)]
+    pub fn standard(&self) -> String {
+        self.standard.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_standard(&mut self, standard: String) {
+        self.standard = standard;
+    }
+
     #[wasm_bindgen(getter)]
     pub fn metadata(&self) -> Option<Metadata> {
         self.metadata.clone()
@@ -225,6 +305,46 @@ impl Contract {
         };
     }
 
+    #[wasm_bindgen(getter)]
+    pub fn constructor_values(&self) -> JsValue {
+        let json = serde_json::to_string(&self.constructor_values).expect("Constructor values are always serializable");
+        js_sys::JSON::parse(&json).unwrap_or(JsValue::NULL)
+    }
+
+    ///Accepts an object mapping field names to JSON values, e.g. `{ cap: "1000000", owner: "0x..." }`.
+    /// Throws when the value is not such an object, `null` and `undefined` clear the values
+    #[wasm_bindgen(setter)]
+    pub fn set_constructor_values(&mut self, values: JsValue) -> Result<(), JsValue> {
+        self.constructor_values = from_js_object(&values)
+            .map_err(|error| JsValue::from_str(&ConstructorValueError::new(&error).to_string()))?;
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sources(&self) -> JsValue {
+        let json = serde_json::to_string(&self.sources).expect("Sources are always serializable");
+        js_sys::JSON::parse(&json).unwrap_or(JsValue::NULL)
+    }
+
+    ///Accepts an object mapping template paths to their content, e.g. `{ "PSP22/lib.rs": "..." }`.
+    /// Throws when the value is not such an object, `null` and `undefined` clear the sources
+    #[wasm_bindgen(setter)]
+    pub fn set_sources(&mut self, sources: JsValue) -> Result<(), JsValue> {
+        self.sources = from_js_object(&sources)
+            .map_err(|error| JsValue::from_str(&format!("Invalid sources: {}", error)))?;
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn cache_version(&self) -> Option<String> {
+        self.cache_version.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_cache_version(&mut self, cache_version: Option<String>) {
+        self.cache_version = cache_version;
+    }
+
     #[wasm_bindgen(getter)]
     pub fn license_name(&self) -> String {
         self.license_name.clone()
@@ -236,6 +356,29 @@ impl Contract {
     }
 }
 
+impl From<Contract> for GenerationRequest {
+    fn from(contract: Contract) -> Self {
+        GenerationRequest {
+            standard: contract.standard,
+            metadata: contract.metadata,
+            extensions: contract.extensions,
+            files: contract.files,
+            source: contract.source,
+            sources: contract.sources,
+            license_name: contract.license_name,
+            use_external_crate: contract.use_external_crate,
+            conflict_policy: contract.conflict_policy,
+            constructor_values: contract.constructor_values,
+            cache: CacheConfig {
+                enabled: contract.cache_enabled,
+                backend: contract.cache_backend,
+                ttl: contract.cache_ttl,
+                version: contract.cache_version,
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 #[wasm_bindgen]
 pub struct MergedFile {
@@ -274,6 +417,18 @@ pub struct ParserResponse {
 
     #[wasm_bindgen(skip)]
     pub files: Vec<MergedFile>,
+
+    #[wasm_bindgen(skip)]
+    pub extensions: Vec<ResolvedExtension>,
+
+    #[wasm_bindgen(skip)]
+    pub conflicts: Vec<MergeConflict>,
+
+    #[wasm_bindgen(skip)]
+    pub report: MergeReport,
+
+    #[wasm_bindgen(skip)]
+    pub constructors: Vec<ConstructorSignature>,
 }
 
 // Define a struct to represent a file
@@ -282,7 +437,7 @@ pub struct ParserResponse {
 impl ParserResponse {
     #[wasm_bindgen(constructor)]
     pub fn new(result: bool, message: String, files: js_sys::Array) -> ParserResponse {
-        Self { result, message, files: files.iter().filter_map(MergedFile::from_js_value).collect() }
+        Self { result, message, files: files.iter().filter_map(MergedFile::from_js_value).collect(), extensions: Vec::new(), conflicts: Vec::new(), report: MergeReport::default(), constructors: Vec::new() }
     }
 
     #[wasm_bindgen(getter)]
@@ -321,39 +476,139 @@ impl ParserResponse {
     pub fn set_files(&mut self, files: js_sys::Array) {
         self.files = files.iter().filter_map(MergedFile::from_js_value).collect();
     }
+
+    ///Extensions used to generate the contract, including the ones added as dependencies
+    /// Each entry has `name`, `reason` ("requested" or "dependency") and optional `required_by` field
+    #[wasm_bindgen(getter)]
+    pub fn extensions(&self) -> JsValue {
+        let extensions_array = self.extensions.iter().map(|extension| {
+            let obj = js_sys::Object::new();
+            js_sys::Reflect::set(&obj, &JsValue::from_str("name"), &JsValue::from_str(&extension.kind.path())).unwrap();
+            match &extension.reason {
+                InclusionReason::Requested => {
+                    js_sys::Reflect::set(&obj, &JsValue::from_str("reason"), &JsValue::from_str("requested")).unwrap();
+                }
+                InclusionReason::RequiredBy(kind) => {
+                    js_sys::Reflect::set(&obj, &JsValue::from_str("reason"), &JsValue::from_str("dependency")).unwrap();
+                    js_sys::Reflect::set(&obj, &JsValue::from_str("required_by"), &JsValue::from_str(&kind.path())).unwrap();
+                }
+            }
+            JsValue::from(obj)
+        }).collect::<js_sys::Array>();
+
+        JsValue::from(extensions_array)
+    }
+
+    ///Elements of the contract contested by more than one extension
+    /// Each entry has `kind` ("function", "field", "impl" or item kind, e.g. "event"), `name`, optional `impl_block`, `extensions` and `reason` fields
+    #[wasm_bindgen(getter)]
+    pub fn conflicts(&self) -> JsValue {
+        let conflicts_array = self.conflicts.iter().map(|conflict| {
+            let obj = js_sys::Object::new();
+            let (kind, name) = match &conflict.target {
+                ConflictTarget::Function { impl_block, name } => {
+                    js_sys::Reflect::set(&obj, &JsValue::from_str("impl_block"), &JsValue::from_str(impl_block)).unwrap();
+                    ("function", name)
+                }
+                ConflictTarget::Field { name } => ("field", name),
+                ConflictTarget::Impl { name } => ("impl", name),
+                ConflictTarget::Item { kind, name } => (kind.as_str(), name),
+            };
+            let extensions = conflict.extensions.iter()
+                .map(|extension| extension.as_ref().map(|kind| JsValue::from(kind.path())).unwrap_or(JsValue::NULL))
+                .collect::<js_sys::Array>();
+            js_sys::Reflect::set(&obj, &JsValue::from_str("kind"), &JsValue::from_str(kind)).unwrap();
+            js_sys::Reflect::set(&obj, &JsValue::from_str("name"), &JsValue::from_str(name)).unwrap();
+            js_sys::Reflect::set(&obj, &JsValue::from_str("extensions"), &JsValue::from(extensions)).unwrap();
+            js_sys::Reflect::set(&obj, &JsValue::from_str("reason"), &JsValue::from_str(&conflict.reason)).unwrap();
+            JsValue::from(obj)
+        }).collect::<js_sys::Array>();
+
+        JsValue::from(conflicts_array)
+    }
+
+    ///Changes made by every extension (storage fields, constructor params, functions, impl blocks, items and uses) serialized as JSON
+    #[wasm_bindgen(getter)]
+    pub fn report(&self) -> String {
+        self.report.to_json()
+    }
+
+    ///Signatures of generated constructors serialized as JSON. Every param has `name`, `ty`, `required` and `extension` fields
+    #[wasm_bindgen(getter)]
+    pub fn constructors(&self) -> String {
+        constructors_to_json(&self.constructors)
+    }
+}
+
+impl From<GeneratedProject> for ParserResponse {
+    fn from(project: GeneratedProject) -> Self {
+        ParserResponse {
+            result: true,
+            message: String::new(),
+            files: project.files,
+            extensions: project.extensions,
+            conflicts: project.conflicts,
+            report: project.report,
+            constructors: project.constructors,
+        }
+    }
+}
+
+impl From<GenerateError> for ParserResponse {
+    fn from(error: GenerateError) -> Self {
+        let message = error.to_string();
+        match error {
+            //Merged contract is still reported, only the files are missing
+            GenerateError::StaticContent { project, .. } => ParserResponse { message, ..ParserResponse::from(project) },
+            GenerateError::Merge { conflicts, .. } => ParserResponse {
+                result: false,
+                message,
+                files: Vec::new(),
+                extensions: Vec::new(),
+                conflicts,
+                report: MergeReport::default(),
+                constructors: Vec::new(),
+            },
+        }
+    }
 }
 
 #[wasm_bindgen]
 pub async fn start(input: Contract) -> ParserResponse {
     panic::set_hook(Box::new(console_error_panic_hook::hook));
 
-    let standard = input.standard;
-    let source = &input.source.clone();
-    let license = &input.license_name.clone();
-    let files_to_process = match input.use_external_crate {
-        true => vec![OutputFile::Main, OutputFile::Cargo], //Generate code that uses external crate - only lib.rs is created
-        false => input.files.to_vec()
-    };
+    match generate(GenerationRequest::from(input)).await {
+        Ok(project) => ParserResponse::from(project),
+        Err(error) => ParserResponse::from(error),
+    }
+}
 
-    match run(input).await {
-        Ok(code) => {
-            match with_static_content(code, license, source, standard, files_to_process).await {
-                Ok(downloaded_files) => ParserResponse {
-                    result: true,
-                    message: String::new(),
-                    files: downloaded_files,
-                },
-                Err(error) => ParserResponse {
-                    result: true,
-                    message: error.to_string(),
-                    files: Vec::new(),
-                }
-            }
-        }
-        Err(error) => ParserResponse {
-            result: false,
-            message: error.to_string(),
-            files: Vec::new(),
-        }
+fn constructors_to_json(constructors: &[ConstructorSignature]) -> String {
+    serde_json::to_string(constructors).expect("Constructor signatures are always serializable")
+}
+
+///Extensions available for the standard serialized as JSON. Every entry has `name`, `title`, `description`,
+/// `dependencies`, `conflicts`, `files`, `constructor_params`, `excluded_constructors` and `standards` fields.
+/// Constructor params of the selected extensions can be rendered before the contract is generated,
+/// the final signatures are returned with the generated contract.
+/// Fragments are cached like templates of the contract, omitted `cache_*` params take the defaults of [`Contract`]
+#[wasm_bindgen]
+pub async fn list_extensions(standard: String, source: String, cache_enabled: Option<bool>, cache_backend: Option<CacheBackend>, cache_ttl: Option<f64>, cache_version: Option<String>) -> Result<String, JsValue> {
+    let defaults = CacheConfig::default();
+    let cache = CacheConfig {
+        enabled: cache_enabled.unwrap_or(defaults.enabled),
+        backend: cache_backend.unwrap_or(defaults.backend),
+        ttl: cache_ttl.or(defaults.ttl),
+        version: cache_version,
+    };
+    match describe_extensions(default_loader(&source, &cache).as_ref(), &standard).await {
+        Ok(extensions) => Ok(serde_json::to_string(&extensions).expect("Extension descriptors are always serializable")),
+        Err(error) => Err(JsValue::from_str(&error.to_string())),
     }
 }
+
+///Removes templates cached by every cache backend
+#[wasm_bindgen]
+pub async fn clear_cache() {
+    clear_cached_files().await;
+}
@@ -1,4 +1,6 @@
-use syn::File;
+use proc_macro2::LineColumn;
+use syn::{File, Item};
+use syn::__private::ToTokens;
 
 /// Converts a Rust syntax tree back into source code, with some formatting adjustments.
 ///
@@ -52,7 +54,9 @@ pub fn unparse(file: &File) -> String {
             }
         }
 
-        result.push('\n');
+        if key > 0 {
+            result.push('\n');
+        }
         result.push_str(line);
     }
 
@@ -97,3 +101,93 @@ pub fn doc_comments_remove(input: &str) -> String {
         .collect::<Vec<_>>()
         .join("\n")
 }
+
+/// Converts a syntax tree parsed from `source` and changed afterwards back into source code.
+///
+/// Items which are not changed are copied from `source` as they are, together with the text
+/// between consecutive untouched items, so only new or changed items are formatted with [unparse].
+/// Documentation comments are removed from the whole result, the same as for files which are not changed at all.
+///
+/// # Examples
+///
+/// ```
+/// let source = "pub struct Data {\n  value: u8\n}\n";
+/// let mut file = syn::parse_file(source).unwrap();
+/// file.items.push(syn::parse_quote!(pub struct Extra;));
+/// let formatted_src = ink_generator::prettifier::unparse_preserving(source, &file);
+/// assert_eq!(formatted_src, "pub struct Data {\n  value: u8\n}\n\npub struct Extra;");
+/// ```
+pub fn unparse_preserving(source: &str, file: &File) -> String {
+    let Ok(original) = syn::parse_file(source) else {
+        return unparse(file);
+    };
+    let line_starts = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(idx, _)| idx + 1))
+        .collect::<Vec<_>>();
+    let ranges = original.items.iter()
+        .map(|item| item_range(source, &line_starts, item))
+        .collect::<Option<Vec<_>>>();
+    let Some(ranges) = ranges else {
+        return unparse(file);
+    };
+    let mut original_items = original.items.iter()
+        .map(|item| Some(item.to_token_stream().to_string()))
+        .collect::<Vec<_>>();
+
+    let mut result = match ranges.first() {
+        Some((start, _)) => source[..*start].to_owned(),
+        None => source.to_owned(),
+    };
+    let mut previous: Option<usize> = None;
+    let mut contiguous = true;
+    for item in &file.items {
+        let tokens = item.to_token_stream().to_string();
+        match original_items.iter().position(|original| original.as_deref() == Some(tokens.as_str())) {
+            Some(idx) => {
+                original_items[idx] = None;
+                let (start, end) = ranges[idx];
+                match previous {
+                    None if contiguous && idx == 0 => {}
+                    Some(previous) if contiguous && previous + 1 == idx => result.push_str(&source[ranges[previous].1..start]),
+                    _ => separate(&mut result),
+                }
+                result.push_str(&source[start..end]);
+                previous = Some(idx);
+                contiguous = true;
+            }
+            None => {
+                let rendered = unparse(&File { shebang: None, attrs: Vec::new(), items: vec![item.clone()] });
+                separate(&mut result);
+                result.push_str(&rendered);
+                contiguous = false;
+            }
+        }
+    }
+    match previous {
+        Some(idx) if contiguous && idx + 1 == ranges.len() => result.push_str(&source[ranges[idx].1..]),
+        _ => {}
+    }
+
+    doc_comments_remove(&result)
+}
+
+fn separate(result: &mut String) {
+    if !result.trim().is_empty() {
+        result.truncate(result.trim_end().len());
+        result.push_str("\n\n");
+    }
+}
+
+fn item_range(source: &str, line_starts: &[usize], item: &Item) -> Option<(usize, usize)> {
+    let tokens = item.to_token_stream().into_iter().collect::<Vec<_>>();
+    let start = byte_offset(source, line_starts, tokens.first()?.span().start())?;
+    let end = byte_offset(source, line_starts, tokens.last()?.span().end())?;
+    (start < end).then_some((start, end))
+}
+
+fn byte_offset(source: &str, line_starts: &[usize], position: LineColumn) -> Option<usize> {
+    let line_start = *line_starts.get(position.line.checked_sub(1)?)?;
+    let line = &source[line_start..];
+    let column = line.char_indices().nth(position.column).map(|(idx, _)| idx).unwrap_or(line.len());
+    Some(line_start + column)
+}
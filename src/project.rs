@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::{ConflictPolicy, get_all_files, MergedFile, Metadata, OutputFile};
+use crate::code_loader::cache_proxy::CacheConfig;
+use crate::code_loader::loader::{default_loader, MemoryLoader, SourceLoader};
+use crate::code_loader::static_files::with_static_content;
+use crate::generator::dependencies::ResolvedExtension;
+use crate::generator::merge::conflicts::{MergeConflict, MergeConflictError};
+use crate::generator::merge::constructor_params::ConstructorSignature;
+use crate::generator::merge::constructor_values::ConstructorValues;
+use crate::generator::merge::report::MergeReport;
+use crate::generator::source_parser::run;
+
+///Contract to generate. Native counterpart of the wasm `Contract`
+#[derive(Debug, Clone)]
+pub struct GenerationRequest {
+    ///Name of the standard, resolved with the standards registry of the source
+    pub standard: String,
+    pub metadata: Option<Metadata>,
+    pub extensions: Vec<String>,
+    ///Files to generate, ignored when the external crate is used
+    pub files: Vec<OutputFile>,
+    ///Root of the templates: URL in the browser, URL or directory in Node.js, directory natively
+    pub source: String,
+    ///Templates passed inline, keyed by path relative to the templates root. When not empty nothing is loaded from `source`
+    pub sources: HashMap<String, String>,
+    pub license_name: String,
+    pub use_external_crate: bool,
+    pub conflict_policy: ConflictPolicy,
+    ///Values of extension storage fields baked into constructors, keyed by field name
+    pub constructor_values: ConstructorValues,
+    ///Cache of the downloaded templates
+    pub cache: CacheConfig,
+}
+
+impl GenerationRequest {
+    ///Request generating every file of the standard, without metadata and with conflicts failing the merge
+    pub fn new(standard: &str, extensions: Vec<String>, source: &str) -> GenerationRequest {
+        GenerationRequest {
+            standard: standard.to_owned(),
+            metadata: None,
+            extensions,
+            files: get_all_files(),
+            source: source.to_owned(),
+            sources: HashMap::new(),
+            license_name: String::new(),
+            use_external_crate: false,
+            conflict_policy: ConflictPolicy::Fail,
+            constructor_values: ConstructorValues::new(),
+            cache: CacheConfig::default(),
+        }
+    }
+
+    ///Inline sources take precedence over the source, which is loaded with the loader matching the environment
+    pub fn loader(&self) -> Box<dyn SourceLoader> {
+        match self.sources.is_empty() {
+            true => default_loader(&self.source, &self.cache),
+            false => Box::new(MemoryLoader::new(self.sources.clone())),
+        }
+    }
+
+    fn files_to_process(&self) -> Vec<OutputFile> {
+        match self.use_external_crate {
+            true => vec![OutputFile::Main, OutputFile::Cargo], //Generate code that uses external crate - only lib.rs is created
+            false => self.files.to_vec()
+        }
+    }
+}
+
+///Generated contract together with the details of the merge
+#[derive(Debug)]
+pub struct GeneratedProject {
+    pub files: Vec<MergedFile>,
+    ///Extensions used to generate the contract, including the ones added as dependencies
+    pub extensions: Vec<ResolvedExtension>,
+    ///Conflicts reported as warnings, see [ConflictPolicy::Warn]
+    pub conflicts: Vec<MergeConflict>,
+    pub report: MergeReport,
+    pub constructors: Vec<ConstructorSignature>,
+}
+
+impl GeneratedProject {
+    pub fn file(&self, name: &str) -> Option<&str> {
+        self.files.iter().find(|file| file.name == name).map(|file| file.content.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub enum GenerateError {
+    ///Contract could not be merged. Conflicts are present when the merge failed because of them
+    Merge {
+        error: Box<dyn Error>,
+        conflicts: Vec<MergeConflict>,
+    },
+    ///Contract was merged, but the static files could not be completed. Project has no files
+    StaticContent {
+        error: Box<dyn Error>,
+        project: GeneratedProject,
+    },
+}
+
+impl fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GenerateError::Merge { error, .. } => write!(f, "{}", error),
+            GenerateError::StaticContent { error, .. } => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for GenerateError {}
+
+///Generates the contract with templates loaded by the loader matching the request, see [GenerationRequest::loader]
+pub async fn generate(request: GenerationRequest) -> Result<GeneratedProject, GenerateError> {
+    let loader = request.loader();
+    generate_with(&request, loader.as_ref()).await
+}
+
+///Generates the contract with templates loaded by the given loader
+pub async fn generate_with(request: &GenerationRequest, loader: &dyn SourceLoader) -> Result<GeneratedProject, GenerateError> {
+    let output = run(request, loader).await.map_err(|error| GenerateError::Merge {
+        conflicts: error.downcast_ref::<MergeConflictError>()
+            .map(|conflict_error| conflict_error.conflicts.clone())
+            .unwrap_or_default(),
+        error,
+    })?;
+
+    let files = with_static_content(&output.files, &request.license_name, loader, &output.standard, request.files_to_process(), &output.error_variants).await;
+    let mut project = GeneratedProject {
+        files: Vec::new(),
+        extensions: output.extensions,
+        conflicts: output.conflicts,
+        report: output.report,
+        constructors: output.constructors,
+    };
+    match files {
+        Ok(files) => {
+            project.files = files;
+            Ok(project)
+        }
+        Err(error) => Err(GenerateError::StaticContent { error, project }),
+    }
+}
@@ -0,0 +1,105 @@
+//! Tests of the command line interface run against templates in a temporary directory.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const BASE_CONTRACT: &str = r#"
+#[ink::contract]
+mod token {
+    #[ink(storage)]
+    pub struct Token {
+        supply: u128,
+    }
+
+    impl Token {
+        #[ink(constructor)]
+        pub fn new(supply: u128) -> Self {
+            Self { supply }
+        }
+    }
+}
+"#;
+
+const OWNABLE: &str = r#"
+#[smart_beaver::extension]
+mod ownable {
+    #[smart_beaver::storage]
+    pub struct Token {
+        owner: AccountId,
+    }
+}
+"#;
+
+const MINTABLE: &str = r#"
+#[smart_beaver::extension(title = "Mintable", description = "Tokens can be minted")]
+mod mintable {
+    impl Token {
+        #[ink(message)]
+        pub fn mint(&mut self, value: u128) {
+            self.supply += value;
+        }
+    }
+}
+"#;
+
+fn templates(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("ink_generator_cli_{}_{}", name, std::process::id()));
+    fs::create_dir_all(root.join("PSP22/extensions/security")).unwrap();
+    fs::write(root.join("PSP22/lib.rs"), BASE_CONTRACT).unwrap();
+    fs::write(root.join("PSP22/extensions/mintable.trs"), MINTABLE).unwrap();
+    fs::write(root.join("PSP22/extensions/security/ownable.trs"), OWNABLE).unwrap();
+    root
+}
+
+fn cli(templates: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ink-generator"))
+        .args(args)
+        .arg("--templates")
+        .arg(templates)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn generated_project_is_written_and_compared() {
+    let root = templates("generate");
+    let out = root.join("my_token");
+    let out_arg = out.to_str().unwrap();
+
+    let generated = cli(&root, &["generate", "--standard", "PSP22", "--ext", "mintable", "--files", "lib.rs", "--out", out_arg]);
+    assert!(generated.status.success(), "{}", String::from_utf8_lossy(&generated.stderr));
+    let code = fs::read_to_string(out.join("lib.rs")).unwrap();
+    assert!(code.contains("pub fn mint(&mut self, value: u128)"));
+    assert!(String::from_utf8_lossy(&generated.stdout).contains("security/ownable (required by mintable)"));
+
+    assert!(cli(&root, &["diff", "--standard", "PSP22", "--ext", "mintable", "--files", "lib.rs", "--out", out_arg]).status.success());
+    fs::write(out.join("lib.rs"), code.replace("value: u128", "amount: u128")).unwrap();
+    let diff = cli(&root, &["diff", "--standard", "PSP22", "--ext", "mintable", "--files", "lib.rs", "--out", out_arg]);
+    assert!(!diff.status.success());
+    assert!(String::from_utf8_lossy(&diff.stdout).contains("-        pub fn mint(&mut self, amount: u128) {\n+        pub fn mint(&mut self, value: u128) {"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn selection_is_validated_without_writing_files() {
+    let root = templates("validate");
+
+    let valid = cli(&root, &["validate", "--standard", "PSP22", "--ext", "mintable"]);
+    assert!(valid.status.success(), "{}", String::from_utf8_lossy(&valid.stderr));
+    assert!(String::from_utf8_lossy(&valid.stdout).starts_with("Valid PSP22 contract with extensions:"));
+
+    let invalid = cli(&root, &["validate", "--standard", "PSP22", "--ext", "mintable,wrapper"]);
+    assert!(!invalid.status.success());
+    assert!(String::from_utf8_lossy(&invalid.stderr).starts_with("error: "));
+
+    let misspelled = cli(&root, &["validate", "--standard", "PSP22", "--exts", "mintable"]);
+    assert!(!misspelled.status.success());
+    assert_eq!(String::from_utf8_lossy(&misspelled.stderr).trim(), "error: Unknown option: --exts, see ink-generator help");
+
+    let standards = cli(&root, &["list-standards", "--json"]);
+    assert_eq!(String::from_utf8_lossy(&standards.stdout).trim(), r#"["PSP22","PSP34","PSP37"]"#);
+
+    fs::remove_dir_all(root).unwrap();
+}
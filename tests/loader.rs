@@ -0,0 +1,167 @@
+//! Tests of the template loader run against an HTTP stand-in.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use futures::executor::block_on;
+use futures::future::{FutureExt, LocalBoxFuture};
+
+use ink_generator::code_loader::cache_proxy::{CacheBackend, CacheConfig};
+use ink_generator::code_loader::loader::{DownloadError, FetchLoader, FsLoader, HttpClient, HttpResponse, is_not_found, SourceLoader};
+use ink_generator::generator::registry::load_registry;
+
+#[derive(Clone)]
+struct PublishedFile {
+    content: String,
+    etag: Option<String>,
+    last_modified: String,
+}
+
+///Serves files with `ETag` and `Last-Modified` validators and answers matching conditional requests with 304
+#[derive(Default)]
+struct StandIn {
+    files: RefCell<HashMap<String, PublishedFile>>,
+    ///Headers of every request
+    requests: RefCell<Vec<Vec<(String, String)>>>,
+    offline: Cell<bool>,
+    ///Status every request fails with, e.g. 503
+    failing: Cell<Option<u16>>,
+}
+
+impl StandIn {
+    fn publish(&self, url: &str, content: &str, etag: Option<&str>, last_modified: &str) {
+        self.files.borrow_mut().insert(url.to_owned(), PublishedFile {
+            content: content.to_owned(),
+            etag: etag.map(str::to_owned),
+            last_modified: last_modified.to_owned(),
+        });
+    }
+
+    fn last_request_headers(&self) -> Vec<(String, String)> {
+        self.requests.borrow().last().cloned().unwrap_or_default()
+    }
+}
+
+struct StandInClient(Rc<StandIn>);
+
+impl HttpClient for StandInClient {
+    fn get<'a>(&'a self, url: &'a str, headers: &'a [(&'a str, String)]) -> LocalBoxFuture<'a, Result<HttpResponse, DownloadError>> {
+        async move {
+            let server = &self.0;
+            if server.offline.get() {
+                return Err(DownloadError::new(url, 0));
+            }
+            server.requests.borrow_mut().push(headers.iter().map(|(name, value)| (name.to_string(), value.clone())).collect());
+            if let Some(status) = server.failing.get() {
+                return Ok(HttpResponse { status, body: String::new(), etag: None, last_modified: None });
+            }
+            let header = |name: &str| headers.iter().find(|(header, _)| *header == name).map(|(_, value)| value.clone());
+            let PublishedFile { content, etag, last_modified } = match server.files.borrow().get(url) {
+                Some(file) => file.clone(),
+                None => return Ok(HttpResponse { status: 404, body: String::new(), etag: None, last_modified: None }),
+            };
+            let not_modified = match header("If-None-Match") {
+                Some(requested) => Some(requested) == etag,
+                None => header("If-Modified-Since").is_some_and(|requested| requested == last_modified),
+            };
+            Ok(HttpResponse {
+                status: if not_modified { 304 } else { 200 },
+                body: if not_modified { String::new() } else { content },
+                etag,
+                last_modified: Some(last_modified),
+            })
+        }.boxed_local()
+    }
+}
+
+fn loader(server: &Rc<StandIn>, version: &str) -> FetchLoader {
+    let cache = CacheConfig { backend: CacheBackend::Memory, version: Some(version.to_owned()), ..CacheConfig::default() };
+    FetchLoader::with_client("https://templates", cache, Box::new(StandInClient(server.clone())))
+}
+
+#[test]
+fn cached_templates_are_revalidated_with_etag() {
+    let server = Rc::new(StandIn::default());
+    server.publish("https://templates/PSP22/lib.rs", "mod token {}", Some("\"v1\""), "Mon, 01 Jan 2024 00:00:00 GMT");
+    let loader = loader(&server, "etag");
+
+    assert_eq!(block_on(loader.load("PSP22/lib.rs")).unwrap(), "mod token {}");
+    assert!(server.last_request_headers().is_empty());
+
+    //Unchanged file is not downloaded again
+    assert_eq!(block_on(loader.load("PSP22/lib.rs")).unwrap(), "mod token {}");
+    assert_eq!(server.last_request_headers()[0], ("If-None-Match".to_owned(), "\"v1\"".to_owned()));
+    assert_eq!(server.requests.borrow().len(), 2);
+
+    //Updated file is used immediately
+    server.publish("https://templates/PSP22/lib.rs", "mod updated {}", Some("\"v2\""), "Tue, 02 Jan 2024 00:00:00 GMT");
+    assert_eq!(block_on(loader.load("PSP22/lib.rs")).unwrap(), "mod updated {}");
+    assert_eq!(block_on(loader.load("PSP22/lib.rs")).unwrap(), "mod updated {}");
+    assert_eq!(server.last_request_headers()[0], ("If-None-Match".to_owned(), "\"v2\"".to_owned()));
+}
+
+#[test]
+fn cached_copy_is_used_when_server_is_unreachable() {
+    let server = Rc::new(StandIn::default());
+    server.publish("https://templates/PSP22/lib.rs", "mod token {}", Some("\"v1\""), "Mon, 01 Jan 2024 00:00:00 GMT");
+    let loader = loader(&server, "offline");
+    assert_eq!(block_on(loader.load("PSP22/lib.rs")).unwrap(), "mod token {}");
+
+    server.offline.set(true);
+    assert_eq!(block_on(loader.load("PSP22/lib.rs")).unwrap(), "mod token {}");
+    assert_eq!(block_on(loader.load("PSP22/data.rs")).err().unwrap().to_string(), "Failed to download file: https://templates/PSP22/data.rs, status code: 0");
+
+    //Failing server is treated the same way
+    server.offline.set(false);
+    server.failing.set(Some(503));
+    assert_eq!(block_on(loader.load("PSP22/lib.rs")).unwrap(), "mod token {}");
+    assert_eq!(block_on(loader.load("PSP22/data.rs")).err().unwrap().to_string(), "Failed to download file: https://templates/PSP22/data.rs, status code: 503");
+    server.failing.set(None);
+
+    //Removed file is reported even though it was cached
+    server.files.borrow_mut().clear();
+    assert_eq!(block_on(loader.load("PSP22/lib.rs")).err().unwrap().to_string(), "Failed to download file: https://templates/PSP22/lib.rs, status code: 404");
+}
+
+#[test]
+fn cached_templates_are_revalidated_with_last_modified() {
+    let server = Rc::new(StandIn::default());
+    server.publish("https://templates/PSP22/lib.rs", "mod token {}", None, "Mon, 01 Jan 2024 00:00:00 GMT");
+    let loader = loader(&server, "last_modified");
+    assert_eq!(block_on(loader.load("PSP22/lib.rs")).unwrap(), "mod token {}");
+
+    assert_eq!(block_on(loader.load("PSP22/lib.rs")).unwrap(), "mod token {}");
+    assert_eq!(server.last_request_headers(), vec![("If-Modified-Since".to_owned(), "Mon, 01 Jan 2024 00:00:00 GMT".to_owned())]);
+
+    server.publish("https://templates/PSP22/lib.rs", "mod updated {}", None, "Tue, 02 Jan 2024 00:00:00 GMT");
+    assert_eq!(block_on(loader.load("PSP22/lib.rs")).unwrap(), "mod updated {}");
+}
+
+#[test]
+fn built_in_standards_are_used_only_when_registry_is_not_published() {
+    let server = Rc::new(StandIn::default());
+    let loader = loader(&server, "registry");
+    assert!(block_on(load_registry(&loader)).unwrap().standard("PSP37").is_ok());
+
+    server.offline.set(true);
+    assert_eq!(block_on(load_registry(&loader)).err().unwrap().to_string(), "Invalid standards registry: standards.toml could not be loaded: Failed to download file: https://templates/standards.toml, status code: 0");
+}
+
+#[test]
+fn local_loader_reports_why_file_could_not_be_read() {
+    let root = std::env::temp_dir().join(format!("ink_generator_loader_{}", std::process::id()));
+    std::fs::create_dir_all(root.join("PSP22/extensions")).unwrap();
+    let loader = FsLoader::new(&root);
+
+    let missing = block_on(loader.load("PSP22/lib.rs")).err().unwrap();
+    assert!(is_not_found(missing.as_ref()));
+    //Directory exists, but can not be read as a file
+    let unreadable = block_on(loader.load("PSP22/extensions")).err().unwrap();
+    assert!(!is_not_found(unreadable.as_ref()));
+    assert!(unreadable.to_string().starts_with(&root.join("PSP22/extensions").display().to_string()));
+    std::fs::remove_dir_all(root).unwrap();
+
+    let remote = block_on(FsLoader::new("https://templates").load("PSP22/lib.rs")).err().unwrap();
+    assert_eq!(remote.to_string(), "Unsupported source: https://templates can not be downloaded natively, pass a directory with templates instead");
+}
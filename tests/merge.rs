@@ -0,0 +1,1163 @@
+//! Native tests of the merge pipeline.
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use futures::executor::block_on;
+use syn::__private::ToTokens;
+
+use ink_generator::{ConflictPolicy, Metadata, OutputFile, prettifier, Standard};
+use ink_generator::code_loader::loader::{FsLoader, MemoryLoader, SourceLoader};
+use ink_generator::generator::merge::{AstMerger, ContractFiles, Merger, MergeOutcome};
+use ink_generator::generator::merge::constructor_values::ConstructorValues;
+use ink_generator::generator::merge::extension_descriptor::ExtensionDescriptor;
+use ink_generator::generator::dependencies::{canonical_order, InclusionReason, resolve_extensions};
+use ink_generator::generator::registry::{Registry, StandardDescriptor};
+use ink_generator::generator::source_parser::{describe_extensions, ExtensionContext, ExtensionKind, run};
+use ink_generator::project::{generate, GenerateError, GenerationRequest};
+
+const BASE_CONTRACT: &str = r#"
+#[ink::contract]
+mod token {
+    #[ink(storage)]
+    pub struct Token {
+        supply: u128,
+    }
+
+    impl Token {
+        #[ink(constructor)]
+        pub fn new(supply: u128) -> Self {
+            Self { supply }
+        }
+
+        #[ink(message)]
+        pub fn total_supply(&self) -> u128 {
+            self.supply
+        }
+    }
+}
+"#;
+
+const OWNABLE: &str = r#"
+#[smart_beaver::extension]
+mod ownable {
+    #[smart_beaver::storage]
+    pub struct Token {
+        owner: AccountId,
+    }
+
+    impl Token {
+        fn ensure_owner(&self) {
+            assert_eq!(self.env().caller(), self.owner);
+        }
+    }
+}
+"#;
+
+const MINTABLE: &str = r#"
+#[smart_beaver::extension]
+mod mintable {
+    #[smart_beaver::storage]
+    pub struct Token {
+        #[smart_beaver::init(0)]
+        minted: u128,
+    }
+
+    impl Token {
+        #[ink(message)]
+        pub fn mint(&mut self, value: u128) {
+            self.ensure_owner();
+            self.supply += value;
+        }
+    }
+}
+"#;
+
+const PAUSABLE: &str = r#"
+#[smart_beaver::extension]
+mod pausable {
+    #[smart_beaver::storage]
+    pub struct Token {
+        #[smart_beaver::init(false)]
+        paused: bool,
+    }
+
+    impl Token {
+        #[smart_beaver::append(line = 0)]
+        pub fn mint(&mut self, value: u128) {
+            assert!(!self.paused);
+        }
+    }
+}
+"#;
+
+fn extension(kind: ExtensionKind, source: &str) -> ExtensionContext {
+    ExtensionContext {
+        kind,
+        ast: syn::parse_str(source).unwrap(),
+    }
+}
+
+fn base_contract() -> syn::File {
+    syn::parse_str(BASE_CONTRACT).unwrap()
+}
+
+fn files(main: syn::File) -> ContractFiles {
+    ContractFiles::from([(OutputFile::Main, main)])
+}
+
+///Arguments of [`Merger::merge`] besides the contract and its extensions
+struct MergeOptions {
+    standard: StandardDescriptor,
+    metadata: Option<Metadata>,
+    values: ConstructorValues,
+    single_file_mode: bool,
+    policy: ConflictPolicy,
+}
+
+impl MergeOptions {
+    fn standard(standard: Standard) -> MergeOptions {
+        MergeOptions {
+            standard: StandardDescriptor::builtin(standard),
+            metadata: None,
+            values: ConstructorValues::new(),
+            single_file_mode: false,
+            policy: ConflictPolicy::Fail,
+        }
+    }
+}
+
+impl Default for MergeOptions {
+    fn default() -> MergeOptions {
+        MergeOptions::standard(Standard::PSP22)
+    }
+}
+
+fn merge_with(base: &ContractFiles, extensions: Vec<ExtensionContext>, options: MergeOptions) -> Result<MergeOutcome, Box<dyn Error>> {
+    Merger::merge(base, extensions, &options.standard, &options.metadata, &options.values, options.single_file_mode, options.policy)
+}
+
+fn merge(extensions: Vec<ExtensionContext>) -> String {
+    let base = files(base_contract());
+    let merged = merge_with(&base, extensions, MergeOptions::default()).unwrap();
+    prettifier::unparse(merged.main_file())
+}
+
+#[test]
+fn merge_output_does_not_depend_on_extension_order() {
+    let first = merge(vec![
+        extension(ExtensionKind::Pausable, PAUSABLE),
+        extension(ExtensionKind::Mintable, MINTABLE),
+        extension(ExtensionKind::Ownable, OWNABLE),
+    ]);
+    let second = merge(vec![
+        extension(ExtensionKind::Ownable, OWNABLE),
+        extension(ExtensionKind::Mintable, MINTABLE),
+        extension(ExtensionKind::Pausable, PAUSABLE),
+    ]);
+
+    assert_eq!(first, second);
+    //Pausable is merged after mintable, so its statement lands in the copied `mint` function
+    assert!(first.contains("assert!(! self.paused);\n            self.ensure_owner();"));
+}
+
+#[test]
+fn invalid_extension_selection_is_rejected() {
+    let psp22 = StandardDescriptor::builtin(Standard::PSP22);
+    let error = resolve_extensions(&psp22, &[ExtensionKind::Enumerable]).err().unwrap();
+    assert_eq!(error.to_string(), "Invalid extension selection: enumerable extension is not available for PSP22");
+
+    let error = resolve_extensions(&psp22, &[ExtensionKind::Mintable, ExtensionKind::Wrapper]).err().unwrap();
+    assert_eq!(error.to_string(), "Invalid extension selection: mintable can not be used together with wrapper");
+
+    //Conflict pulled in as a dependency is rejected as well
+    let error = resolve_extensions(&psp22, &[ExtensionKind::AccessControl, ExtensionKind::Burnable]).err().unwrap();
+    assert_eq!(error.to_string(), "Invalid extension selection: security/access_control can not be used together with security/ownable");
+}
+
+#[test]
+fn dependency_cycle_is_rejected() {
+    let registry = Registry::parse(r#"
+[[standards]]
+name = "PSP22"
+extensions = [{ name = "mintable", dependencies = ["burnable"] }, { name = "burnable", dependencies = ["mintable"] }]
+"#).unwrap();
+    let standard = registry.standard("PSP22").unwrap();
+    assert_eq!(resolve_extensions(standard, &[ExtensionKind::Mintable]).unwrap().len(), 2);
+
+    let error = canonical_order(standard, &[ExtensionKind::Mintable, ExtensionKind::Burnable]).err().unwrap();
+    assert_eq!(error.to_string(), "Invalid extension selection: dependency cycle between mintable, burnable");
+}
+
+fn replacing_fragment(name: &str) -> String {
+    format!(r#"
+#[smart_beaver::extension]
+mod {name} {{
+    impl Token {{
+        #[smart_beaver::replace]
+        pub fn total_supply(&self) -> u128 {{
+            0
+        }}
+    }}
+}}
+"#)
+}
+
+#[test]
+fn replacing_the_same_function_twice_is_a_conflict() {
+    let base = files(base_contract());
+    let extensions = vec![
+        extension(ExtensionKind::Burnable, &replacing_fragment("burnable")),
+        extension(ExtensionKind::Capped, &replacing_fragment("capped")),
+    ];
+
+    let error = merge_with(&base, extensions.clone(), MergeOptions::default()).err().unwrap();
+    assert!(error.to_string().contains("function Token::total_supply"));
+
+    let merged = merge_with(&base, extensions, MergeOptions { policy: ConflictPolicy::Warn, ..MergeOptions::default() }).unwrap();
+    assert_eq!(merged.conflicts.len(), 1);
+    assert_eq!(merged.conflicts[0].extensions, vec![Some(ExtensionKind::Burnable), Some(ExtensionKind::Capped)]);
+}
+
+#[test]
+fn report_lists_changes_per_extension() {
+    let base = files(base_contract());
+    let extensions = vec![
+        extension(ExtensionKind::Ownable, OWNABLE),
+        extension(ExtensionKind::Mintable, MINTABLE),
+        extension(ExtensionKind::Pausable, PAUSABLE),
+    ];
+    let report = merge_with(&base, extensions, MergeOptions::default()).unwrap().report;
+
+    let ownable = &report.extensions[0];
+    assert_eq!(ownable.extension, "security/ownable");
+    assert_eq!(ownable.constructor_params, vec!["owner"]);
+    assert_eq!(ownable.functions_copied, vec!["Token::ensure_owner"]);
+
+    let pausable = &report.extensions[2];
+    assert_eq!(pausable.storage_fields, vec!["paused"]);
+    assert!(pausable.constructor_params.is_empty());
+    assert_eq!(pausable.functions_appended, vec!["Token::mint"]);
+}
+
+fn appending_fragment(anchor: &str) -> String {
+    format!(r#"
+#[smart_beaver::extension]
+mod capped {{
+    impl Token {{
+        #[smart_beaver::append({anchor})]
+        pub fn total_supply(&self) -> u128 {{
+            self.ensure_cap();
+        }}
+    }}
+}}
+"#)
+}
+
+#[test]
+fn append_anchors_are_resolved_against_statements() {
+    let base = files(base_contract());
+    let merge_anchor = |anchor: &str| {
+        let extensions = vec![extension(ExtensionKind::Capped, &appending_fragment(anchor))];
+        merge_with(&base, extensions, MergeOptions::default())
+            .map(|merged| prettifier::unparse(merged.main_file()))
+    };
+
+    let expected = "self.ensure_cap();\n            self.supply";
+    assert!(merge_anchor(r#"before = "self.supply""#).unwrap().contains(expected));
+    assert!(merge_anchor("before_return").unwrap().contains(expected));
+
+    let error = merge_anchor(r#"after = "self.data.transfer""#).err().unwrap();
+    assert!(error.to_string().contains("Anchor \"self.data.transfer\" not found in function total_supply"));
+    //Anchors match whole tokens only
+    assert!(merge_anchor(r#"before = "self.supp""#).is_err());
+
+    let error = merge_anchor(r#"befor = "self.supply""#).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: Invalid append argument: befor = \"self.supply\" of function Token::total_supply");
+    let error = merge_anchor("foo = 3").err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: Invalid append argument: foo = 3 of function Token::total_supply");
+    assert!(merge_anchor("before = self.supply").is_err());
+    assert!(merge_anchor(r#"before = "self.supply(""#).err().unwrap().to_string().contains("Invalid append anchor"));
+
+    let error = merge_anchor(r#"after = "self.supply""#).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: Anchor \"self.supply\" is the tail expression of function total_supply, use before_return instead");
+
+    let both = appending_fragment("before_return").replace("#[smart_beaver::append(before_return)]", "#[smart_beaver::append(before_return)]\n        #[smart_beaver::replace]");
+    let error = merge_with(&base, vec![extension(ExtensionKind::Capped, &both)], MergeOptions::default()).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: Function can not be marked with both append and replace of function Token::total_supply");
+}
+
+const PAUSABLE_GUARD: &str = r#"
+#[smart_beaver::extension]
+mod pausable {
+    impl Token {
+        #[smart_beaver::guard(mutable_messages)]
+        fn when_not_paused(&self) {
+            assert!(!self.paused);
+        }
+
+        #[ink(message)]
+        pub fn pause(&mut self) {
+            self.paused = true;
+        }
+    }
+}
+"#;
+
+#[test]
+fn guard_is_injected_into_mutable_messages() {
+    let base = files(base_contract());
+    let extensions = vec![
+        extension(ExtensionKind::Ownable, OWNABLE),
+        extension(ExtensionKind::Mintable, MINTABLE),
+        extension(ExtensionKind::Pausable, PAUSABLE_GUARD),
+    ];
+    let merged = merge_with(&base, extensions, MergeOptions::default()).unwrap();
+    let code = prettifier::unparse(merged.main_file());
+
+    assert_eq!(code.matches("assert!(! self.paused);").count(), 1);
+    assert!(!code.contains("when_not_paused"));
+    assert_eq!(merged.report.extensions[2].functions_guarded, vec!["Token::mint"]);
+
+    for (arguments, invalid) in [("mutable_message", "mutable_message"), ("mutable_messages, excpt = [mint]", "excpt = [mint]"), ("messages = mint", "messages = mint"), ("\"mint\"", "\"mint\"")] {
+        let guard = extension(ExtensionKind::Pausable, &PAUSABLE_GUARD.replace("guard(mutable_messages)", &format!("guard({})", arguments)));
+        let error = merge_with(&base, vec![guard], MergeOptions::default()).err().unwrap();
+        assert_eq!(error.to_string(), format!("Merge failed: Invalid guard argument: {} of function when_not_paused", invalid));
+    }
+}
+
+#[test]
+fn removed_items_must_not_be_referenced() {
+    let base = files(base_contract());
+    let merge_fragment = |fragment: &str| {
+        let extensions = vec![extension(ExtensionKind::Wrapper, fragment)];
+        merge_with(&base, extensions, MergeOptions::default())
+            .map(|merged| prettifier::unparse(merged.main_file()))
+    };
+
+    let code = merge_fragment(r#"
+        #[smart_beaver::extension]
+        mod wrapper {
+            impl Token {
+                #[smart_beaver::remove]
+                pub fn total_supply(&self) -> u128 {}
+            }
+        }
+    "#).unwrap();
+    assert!(!code.contains("total_supply"));
+
+    let error = merge_fragment(r#"
+        #[smart_beaver::extension]
+        mod wrapper {
+            #[smart_beaver::storage]
+            pub struct Token {
+                #[smart_beaver::remove]
+                supply: u128,
+            }
+        }
+    "#).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: storage field supply removed by wrapper is still referenced");
+}
+
+#[test]
+fn removed_fields_are_dropped_from_storage_literals_bound_with_let() {
+    let contract = BASE_CONTRACT
+        .replace("        supply: u128,\n    }", "        supply: u128,\n        owner: AccountId,\n    }")
+        .replace("            Self { supply }", "            let instance = Self { supply, owner: Self::env().caller() };\n            instance");
+    let wrapper = extension(ExtensionKind::Wrapper, r#"
+        #[smart_beaver::extension]
+        mod wrapper {
+            #[smart_beaver::storage]
+            pub struct Token {
+                #[smart_beaver::remove]
+                owner: AccountId,
+            }
+        }
+    "#);
+
+    let merged = merge_with(&files(syn::parse_str(&contract).unwrap()), vec![wrapper], MergeOptions::default()).unwrap();
+    let code = prettifier::unparse(merged.main_file());
+    assert!(code.contains("let instance = Self { supply };"));
+    assert!(!code.contains("owner"));
+}
+
+#[test]
+fn on_missing_decides_what_happens_to_absent_targets() {
+    let base = files(base_contract());
+    let merge_strategy = |strategy: &str| {
+        let fragment = format!(r#"
+            #[smart_beaver::extension]
+            mod capped {{
+                impl Token {{
+                    #[smart_beaver::{strategy}]
+                    pub fn cap(&self) -> u128 {{
+                        0
+                    }}
+                }}
+            }}
+        "#);
+        let extensions = vec![extension(ExtensionKind::Capped, &fragment)];
+        merge_with(&base, extensions, MergeOptions::default())
+    };
+
+    let copied = prettifier::unparse(merge_strategy("append(line = 0)").unwrap().main_file());
+    assert!(copied.contains("pub fn cap(&self)"));
+    assert!(!copied.contains("smart_beaver"));
+
+    let skipped = merge_strategy(r#"replace(on_missing = "skip")"#).unwrap();
+    assert!(!prettifier::unparse(skipped.main_file()).contains("pub fn cap"));
+    assert_eq!(skipped.report.extensions[0].warnings, vec!["Token::cap skipped - target function not found"]);
+
+    let error = merge_strategy(r#"append(before_return, on_missing = "error")"#).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: Target function Token::cap not found");
+
+    let error = merge_strategy(r#"replace(on_missing = "eror")"#).err().unwrap();
+    assert_eq!(error.to_string(), r#"Merge failed: Unknown on_missing value: "eror", expected "copy", "error" or "skip" of function Token::cap"#);
+
+    let error = merge_strategy(r#"replace(line = 0)"#).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: Invalid replace argument: line = 0 of function Token::cap");
+}
+
+#[test]
+fn module_items_are_copied_from_extensions() {
+    let base = files(base_contract());
+    let fragment = |event_field: &str| format!(r#"
+        #[smart_beaver::extension]
+        mod pausable {{
+            const PAUSE_DELAY: u32 = 10;
+
+            #[ink(event)]
+            pub struct Paused {{
+                {event_field}: AccountId,
+            }}
+        }}
+    "#);
+
+    let extensions = vec![
+        extension(ExtensionKind::Pausable, &fragment("account")),
+        extension(ExtensionKind::Capped, &fragment("account")),
+    ];
+    let merged = merge_with(&base, extensions, MergeOptions::default()).unwrap();
+    let code = prettifier::unparse(merged.main_file());
+    assert_eq!(code.matches("pub struct Paused").count(), 1);
+    assert!(code.find("const PAUSE_DELAY").unwrap() < code.find("impl Token").unwrap());
+    assert_eq!(merged.report.extensions[0].items, vec!["const PAUSE_DELAY", "event Paused"]);
+
+    let extensions = vec![
+        extension(ExtensionKind::Pausable, &fragment("account")),
+        extension(ExtensionKind::Capped, &fragment("caller")),
+    ];
+    let error = merge_with(&base, extensions, MergeOptions::default()).err().unwrap();
+    assert!(error.to_string().contains("event Paused [capped, pausable]"));
+}
+
+#[test]
+fn error_variants_extend_inlined_error_enum() {
+    let base = files(syn::parse_str(&BASE_CONTRACT.replace("    impl Token {", r#"
+    pub enum PSP22Error {
+        Custom(String),
+    }
+
+    impl PSP22Error {
+        fn code(&self) -> u8 {
+            #[smart_beaver::extensible]
+            match self {
+                PSP22Error::Custom(_) => 0,
+            }
+        }
+    }
+
+    impl Token {"#)).unwrap());
+    let capped = extension(ExtensionKind::Capped, r#"
+        #[smart_beaver::extension]
+        mod capped {
+            #[smart_beaver::error_variants]
+            pub enum PSP22Error {
+                CapExceeded,
+            }
+
+            #[smart_beaver::error_variants]
+            impl PSP22Error {
+                fn code(&self) -> u8 {
+                    match self {
+                        PSP22Error::CapExceeded => 10,
+                    }
+                }
+            }
+        }
+    "#);
+
+    let merged = merge_with(&base, vec![capped], MergeOptions::default()).unwrap();
+    let code = prettifier::unparse(merged.main_file());
+    assert!(code.contains("Custom(String),\n        CapExceeded,"));
+    assert!(code.contains("PSP22Error::Custom(_) => 0,\n                PSP22Error::CapExceeded => 10,"));
+    assert!(!code.contains("smart_beaver"));
+    assert!(merged.error_variants.is_empty());
+    assert_eq!(merged.report.extensions[0].error_variants, vec!["PSP22Error::CapExceeded"]);
+}
+
+#[test]
+fn extensions_can_target_other_files() {
+    let base = ContractFiles::from([
+        (OutputFile::Main, base_contract()),
+        (OutputFile::Traits, syn::parse_str("pub trait PSP22 {\n    fn total_supply(&self) -> u128;\n}").unwrap()),
+        (OutputFile::Data, syn::parse_str("pub struct PSP22Data {}\n\nimpl PSP22Data {}").unwrap()),
+    ]);
+    let burnable = extension(ExtensionKind::Burnable, r#"
+        #[smart_beaver::extension(file = "traits.rs")]
+        mod traits {
+            pub trait PSP22 {
+                fn total_supply(&self) -> u128;
+                fn burn(&mut self, value: u128);
+            }
+        }
+
+        #[smart_beaver::extension(file = "data.rs")]
+        mod data {
+            impl PSP22Data {
+                pub fn burn(&mut self, value: u128) {}
+            }
+        }
+    "#);
+
+    let merged = merge_with(&base, vec![burnable.clone()], MergeOptions::default()).unwrap();
+    let traits = prettifier::unparse(&merged.files[&OutputFile::Traits]);
+    assert!(traits.contains("fn total_supply(&self) -> u128;\n\n    fn burn(&mut self, value: u128);"));
+    assert!(prettifier::unparse(&merged.files[&OutputFile::Data]).contains("pub fn burn(&mut self, value: u128) {}"));
+    assert_eq!(merged.report.extensions[0].files, vec!["traits.rs", "data.rs"]);
+    assert_eq!(merged.report.extensions[0].items, vec!["trait item PSP22::burn"]);
+
+    let error = merge_with(&files(base_contract()), vec![burnable], MergeOptions::default()).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: traits.rs extended by burnable is not loaded");
+}
+
+#[test]
+fn error_variants_go_to_the_errors_file_before_other_files() {
+    let error_enum = "pub enum PSP22Error {\n    Custom(String),\n}";
+    let base = ContractFiles::from([
+        (OutputFile::Main, base_contract()),
+        (OutputFile::Data, syn::parse_str(error_enum).unwrap()),
+        (OutputFile::Errors, syn::parse_str(error_enum).unwrap()),
+    ]);
+    let capped = extension(ExtensionKind::Capped, r#"
+        #[smart_beaver::extension(file = "data.rs")]
+        mod capped {
+            #[smart_beaver::error_variants]
+            pub enum PSP22Error {
+                CapExceeded,
+            }
+        }
+    "#);
+
+    for _ in 0..8 {
+        let merged = merge_with(&base.clone(), vec![capped.clone()], MergeOptions::default()).unwrap();
+        assert!(prettifier::unparse(&merged.files[&OutputFile::Errors]).contains("CapExceeded"));
+        assert!(!prettifier::unparse(&merged.files[&OutputFile::Data]).contains("CapExceeded"));
+    }
+}
+
+#[test]
+fn untouched_items_of_extended_files_are_kept_as_they_are() {
+    let data = "use ink::prelude::vec::Vec;\n\n// Balances of the accounts\npub struct PSP22Data {\n  balances: Vec<u128>,\n}\n\nimpl PSP22Data {}\n";
+    let mintable = format!("{MINTABLE}{}", r#"
+#[smart_beaver::extension(file = "data.rs")]
+mod data {
+    impl PSP22Data {
+        pub fn mint(&mut self, value: u128) {}
+    }
+}
+"#);
+    let sources = HashMap::from([
+        ("PSP22/lib.rs".to_owned(), BASE_CONTRACT.to_owned()),
+        ("PSP22/data.rs".to_owned(), data.to_owned()),
+        ("PSP22/extensions/mintable.trs".to_owned(), mintable),
+        ("PSP22/extensions/security/ownable.trs".to_owned(), OWNABLE.to_owned()),
+    ]);
+    let request = GenerationRequest { files: vec![OutputFile::Main, OutputFile::Data], ..inline_request(sources) };
+
+    let project = block_on(generate(request)).unwrap();
+    assert_eq!(project.file("data.rs").unwrap(), "use ink::prelude::vec::Vec;\n\npub struct PSP22Data {\n  balances: Vec<u128>,\n}\n\nimpl PSP22Data {\n    pub fn mint(&mut self, value: u128) {}\n}");
+}
+
+#[test]
+fn every_constructor_is_extended() {
+    let constructors = |extra: &str| files(syn::parse_str(&BASE_CONTRACT.replace("        #[ink(message)]", &format!(r#"
+        #[ink(constructor, payable)]
+        pub fn new_with_supply(supply: u128) -> Self {{
+            Self {{ supply }}
+        }}
+
+        #[ink(constructor)]
+        pub fn new_default() -> Self {{
+            {extra}
+        }}
+
+        #[ink(message)]"#))).unwrap());
+    let ownable = |except: &str| extension(ExtensionKind::Ownable, &OWNABLE.replace("#[smart_beaver::storage]", &format!("#[smart_beaver::storage({except})]")));
+
+    let base = constructors("Self { supply: 0 }");
+    let merged = merge_with(&base, vec![ownable("")], MergeOptions::default()).unwrap();
+    let code = prettifier::unparse(merged.main_file());
+    assert_eq!(code.matches("owner: AccountId) -> Self").count(), 3);
+    assert!(code.contains("Self { supply: 0, owner }"));
+
+    let base = constructors("Self::new(0)");
+    let error = merge_with(&base, vec![ownable("")], MergeOptions::default()).err().unwrap();
+    assert!(error.to_string().contains("Constructor new_default does not end with a `Self { .. }` literal"));
+
+    let merged = merge_with(&base, vec![ownable("except = [new_default]")], MergeOptions::default()).unwrap();
+    assert!(prettifier::unparse(merged.main_file()).contains("pub fn new_default() -> Self {\n            Self::new(0)"));
+}
+
+#[test]
+fn constructor_params_are_ordered_by_priority() {
+    let base = files(base_contract());
+    let capped = extension(ExtensionKind::Capped, r#"
+        #[smart_beaver::extension]
+        mod capped {
+            #[smart_beaver::storage]
+            pub struct Token {
+                #[smart_beaver::param(name = "max_supply", priority = 10, required = false, default = u128::MAX)]
+                cap: u128,
+            }
+        }
+    "#);
+    let ownable = extension(ExtensionKind::Ownable, &OWNABLE.replace("owner: AccountId", "#[smart_beaver::param(name = \"initial_owner\")]\n        owner: AccountId"));
+
+    let merged = merge_with(&base, vec![capped, ownable], MergeOptions::default()).unwrap();
+    let code = prettifier::unparse(merged.main_file());
+    assert!(code.contains("supply: u128,\n            max_supply: Option<u128>,\n            initial_owner: AccountId,\n        ) -> Self"));
+    assert!(code.contains("owner: initial_owner,"));
+    assert!(code.contains("cap: max_supply.unwrap_or_else(|| u128::MAX),"));
+
+    let params = &merged.constructors[0].params;
+    assert_eq!(params.iter().map(|param| param.name.as_str()).collect::<Vec<_>>(), vec!["supply", "max_supply", "initial_owner"]);
+    assert!(!params[1].required);
+    assert_eq!(params[2].extension.as_deref(), Some("security/ownable"));
+
+    let capped = extension(ExtensionKind::Capped, r#"
+        #[smart_beaver::extension]
+        mod capped {
+            #[smart_beaver::storage]
+            pub struct Token {
+                #[smart_beaver::param(required = false)]
+                cap: u128,
+            }
+        }
+    "#);
+    let error = merge_with(&base, vec![capped], MergeOptions::default()).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: Param cap of capped is not required, but has no default value");
+}
+
+#[test]
+fn constructor_values_are_baked_into_constructors() {
+    let base = files(base_contract());
+    let merge_values = |values: serde_json::Value| {
+        let values: ConstructorValues = serde_json::from_value(values).unwrap();
+        let extensions = vec![extension(ExtensionKind::Ownable, OWNABLE), extension(ExtensionKind::Mintable, MINTABLE)];
+        merge_with(&base, extensions, MergeOptions { values, ..MergeOptions::default() })
+    };
+
+    let owner = format!("0x{}", "01".repeat(32));
+    let merged = merge_values(serde_json::json!({ "owner": owner, "minted": "5" })).unwrap();
+    let code = prettifier::unparse(merged.main_file());
+    assert!(code.contains("pub fn new(supply: u128) -> Self"));
+    assert!(code.contains("owner: AccountId::from(") && code.contains("0x01,"));
+    assert!(code.contains("minted: 5,"));
+    assert!(merged.constructors[0].params.iter().all(|param| param.name == "supply"));
+
+    let error = merge_values(serde_json::json!({ "owner": "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY" })).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: Invalid constructor value: owner expects 0x prefixed hex string of 32 bytes, got \"5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY\"");
+
+    let error = merge_values(serde_json::json!({ "cap": 10 })).err().unwrap();
+    assert_eq!(error.to_string(), "Invalid constructor value: cap is not a storage field added by selected extensions");
+
+    let metadata = extension(ExtensionKind::Metadata, r#"
+#[smart_beaver::extension]
+mod metadata {
+    #[smart_beaver::storage]
+    pub struct Token {
+        name: Option<String>,
+        decimals: u8,
+    }
+}
+"#);
+    let values: ConstructorValues = serde_json::from_value(serde_json::json!({ "decimals": 18 })).unwrap();
+    let error = merge_with(&base, vec![metadata], MergeOptions { values, ..MergeOptions::default() }).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: Invalid constructor value: decimals is a PSP22 metadata field, it is filled from metadata, not constructor values");
+}
+
+const PSP34_METADATA: &str = r#"
+#[smart_beaver::extension]
+mod metadata {
+    #[smart_beaver::storage]
+    pub struct Token {
+        #[smart_beaver::init(0)]
+        minted_at: u64,
+        #[smart_beaver::metadata]
+        #[smart_beaver::init(Data::default())]
+        metadata: Data,
+    }
+}
+"#;
+
+fn psp34_base_contract() -> syn::File {
+    syn::parse_str(&BASE_CONTRACT.replacen("    impl Token {", r#"
+    impl crate::PSP34 for Token {
+        fn collection_id(&self) -> crate::Id {
+            crate::Id::U8(0)
+        }
+    }
+
+    impl Token {"#, 1)).unwrap()
+}
+
+fn constructor_stmts(file: &syn::File, name: &str) -> Vec<String> {
+    let syn::Item::Mod(contract) = &file.items[0] else {
+        panic!("Contract mod expected");
+    };
+    contract.content.as_ref().unwrap().1.iter()
+        .filter_map(|item| match item {
+            syn::Item::Impl(impl_block) => Some(impl_block),
+            _ => None
+        })
+        .flat_map(|impl_block| &impl_block.items)
+        .find_map(|impl_item| match impl_item {
+            syn::ImplItem::Fn(impl_fn) if impl_fn.sig.ident == name => Some(impl_fn),
+            _ => None
+        })
+        .unwrap()
+        .block.stmts.iter()
+        .map(|stmt| stmt.to_token_stream().to_string())
+        .collect()
+}
+
+#[test]
+fn psp34_metadata_is_set_as_collection_attributes() {
+    let merge_metadata = |uri: &str, fragment: &str| {
+        let metadata = Some(Metadata::new(Some("Beavers".to_owned()), Some("BVR".to_owned()), Some(uri.to_owned()), None));
+        let extensions = vec![extension(ExtensionKind::Metadata, fragment), extension(ExtensionKind::Mintable, MINTABLE)];
+        merge_with(&files(psp34_base_contract()), extensions, MergeOptions { metadata, ..MergeOptions::standard(Standard::PSP34) })
+    };
+
+    let merged = merge_metadata("ipfs://bafy/{id}.json", PSP34_METADATA).unwrap();
+    let expected: Vec<syn::Stmt> = vec![
+        syn::parse_quote!(let mut instance = Self { supply, minted: 0, minted_at: 0, metadata: Data::default() };),
+        syn::parse_quote!(let collection_id = <Self as crate::PSP34>::collection_id(&instance);),
+        syn::parse_quote!(instance.metadata.set_attribute(collection_id.clone(), String::from("name").into_bytes(), String::from("Beavers").into_bytes());),
+        syn::parse_quote!(instance.metadata.set_attribute(collection_id.clone(), String::from("symbol").into_bytes(), String::from("BVR").into_bytes());),
+        syn::parse_quote!(instance.metadata.set_attribute(collection_id.clone(), String::from("uri_template").into_bytes(), String::from("ipfs://bafy/{id}.json").into_bytes());),
+        syn::Stmt::Expr(syn::parse_quote!(instance), None),
+    ];
+    assert_eq!(constructor_stmts(merged.main_file(), "new"), expected.iter().map(|stmt| stmt.to_token_stream().to_string()).collect::<Vec<_>>());
+
+    let token_uri = constructor_stmts(merged.main_file(), "token_uri");
+    assert!(token_uri[0].contains("crate :: Id :: U8 (id) =>"));
+    let contract = merged.main_file().to_token_stream().to_string();
+    assert!(contract.contains("use ink :: prelude :: string :: String ;"));
+    assert!(contract.contains("pub fn token_uri (& self , id : crate :: Id) -> String"));
+
+    let contract = merge_metadata("ipfs://bafy/", PSP34_METADATA).unwrap().main_file().to_token_stream().to_string();
+    assert!(contract.contains("String :: from (\"base_uri\")"));
+    assert!(!contract.contains("token_uri"));
+
+    let error = merge_metadata("ipfs://bafy/", &PSP34_METADATA.replace("#[smart_beaver::metadata]", "")).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: PSP34 metadata extension has no storage field marked with #[smart_beaver::metadata]");
+
+    let base = ContractFiles::from([
+        (OutputFile::Main, psp34_base_contract()),
+        (OutputFile::Data, syn::parse_str("pub struct Data;\n\nimpl Data {\n    pub fn attribute(&self) {}\n}").unwrap()),
+    ]);
+    let metadata = Some(Metadata::new(Some("Beavers".to_owned()), None, None, None));
+    let error = merge_with(&base, vec![extension(ExtensionKind::Metadata, PSP34_METADATA)], MergeOptions { metadata, ..MergeOptions::standard(Standard::PSP34) }).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: PSP34 metadata type Data has no set_attribute function");
+}
+
+const PSP37_BASE_CONTRACT: &str = r#"
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+mod data;
+mod errors;
+mod traits;
+
+pub use data::{Id, PSP37Data, PSP37Event};
+pub use errors::PSP37Error;
+pub use traits::PSP37;
+
+#[ink::contract]
+mod token {
+    use crate::{Id, PSP37, PSP37Data, PSP37Error, PSP37Event};
+    use ink::prelude::vec::Vec;
+
+    #[ink(event)]
+    pub struct TransferSingle {
+        #[ink(topic)]
+        from: Option<AccountId>,
+        #[ink(topic)]
+        to: Option<AccountId>,
+        id: Id,
+        value: u128,
+    }
+
+    #[ink(storage)]
+    pub struct Token {
+        data: PSP37Data,
+    }
+
+    impl Token {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self {
+                data: PSP37Data::default(),
+            }
+        }
+
+        fn emit_events(&self, events: Vec<PSP37Event>) {
+            for event in events {
+                match event {
+                    PSP37Event::TransferSingle { from, to, id, value } => self.env().emit_event(TransferSingle { from, to, id, value }),
+                }
+            }
+        }
+    }
+
+    impl PSP37 for Token {
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId, id: Option<Id>) -> u128 {
+            self.data.balance_of(owner, id)
+        }
+
+        #[ink(message)]
+        fn transfer(&mut self, to: AccountId, id: Id, value: u128, data: Vec<u8>) -> Result<(), PSP37Error> {
+            let events = self.data.transfer(self.env().caller(), to, id, value, data)?;
+            self.emit_events(events);
+            Ok(())
+        }
+    }
+}
+"#;
+
+fn psp37_fragments() -> Vec<(ExtensionKind, &'static str)> {
+    vec![
+        (ExtensionKind::Ownable, r#"
+            #[smart_beaver::extension]
+            mod ownable {
+                #[smart_beaver::storage]
+                pub struct Token {
+                    #[smart_beaver::param(name = "initial_owner", priority = 0)]
+                    owner: AccountId,
+                }
+
+                impl Token {
+                    fn ensure_owner(&self) -> Result<(), PSP37Error> {
+                        match self.env().caller() == self.owner {
+                            true => Ok(()),
+                            false => Err(PSP37Error::Custom(String::from("Caller is not the owner"))),
+                        }
+                    }
+                }
+            }
+        "#),
+        (ExtensionKind::Mintable, r#"
+            #[smart_beaver::extension]
+            mod mintable {
+                impl Token {
+                    #[ink(message)]
+                    pub fn mint(&mut self, to: AccountId, id: Id, value: u128) -> Result<(), PSP37Error> {
+                        self.ensure_owner()?;
+                        let events = self.data.mint(to, id, value)?;
+                        self.emit_events(events);
+                        Ok(())
+                    }
+                }
+            }
+        "#),
+        (ExtensionKind::Burnable, r#"
+            #[smart_beaver::extension]
+            mod burnable {
+                impl Token {
+                    #[ink(message)]
+                    pub fn burn(&mut self, from: AccountId, id: Id, value: u128) -> Result<(), PSP37Error> {
+                        self.ensure_owner()?;
+                        let events = self.data.burn(from, id, value)?;
+                        self.emit_events(events);
+                        Ok(())
+                    }
+                }
+            }
+        "#),
+        (ExtensionKind::Batch, r#"
+            #[smart_beaver::extension]
+            mod batch {
+                impl Token {
+                    #[ink(message)]
+                    pub fn batch_transfer(&mut self, to: AccountId, ids_amounts: Vec<(Id, u128)>, data: Vec<u8>) -> Result<(), PSP37Error> {
+                        let events = self.data.batch_transfer(self.env().caller(), to, ids_amounts, data)?;
+                        self.emit_events(events);
+                        Ok(())
+                    }
+                }
+            }
+        "#),
+        (ExtensionKind::Metadata, r#"
+            #[smart_beaver::extension]
+            mod metadata {
+                #[smart_beaver::storage]
+                pub struct Token {
+                    #[smart_beaver::init(Default::default())]
+                    attributes: ink::storage::Mapping<(Id, Vec<u8>), Vec<u8>>,
+                }
+
+                impl Token {
+                    #[ink(message)]
+                    pub fn get_attribute(&self, id: Id, key: Vec<u8>) -> Option<Vec<u8>> {
+                        self.attributes.get((id, key))
+                    }
+                }
+            }
+        "#),
+    ]
+}
+
+#[test]
+fn psp37_contract_is_merged_with_its_extensions() {
+    let psp37 = StandardDescriptor::builtin(Standard::PSP37);
+    let selection = resolve_extensions(&psp37, &[ExtensionKind::Batch, ExtensionKind::Metadata]).unwrap();
+    let fragments = psp37_fragments();
+    let merge_psp37 = |metadata: Option<Metadata>, single_file_mode: bool| {
+        let extensions = selection.iter()
+            .map(|resolved| {
+                let (kind, source) = fragments.iter().find(|(kind, _)| *kind == resolved.kind).unwrap();
+                extension(kind.clone(), source)
+            })
+            .collect();
+        merge_with(&files(syn::parse_str(PSP37_BASE_CONTRACT).unwrap()), extensions, MergeOptions { metadata, single_file_mode, ..MergeOptions::standard(Standard::PSP37) })
+    };
+
+    let merged = merge_psp37(None, false).unwrap();
+    let code = prettifier::unparse(merged.main_file());
+    assert!(code.contains("pub fn new(initial_owner: AccountId) -> Self"));
+    assert!(code.contains("owner: initial_owner,"));
+    assert!(code.contains("attributes: Default::default(),"));
+    for message in ["pub fn mint(", "pub fn burn(", "pub fn batch_transfer(", "pub fn get_attribute("] {
+        assert_eq!(code.matches(message).count(), 1, "{} is merged once", message);
+    }
+    assert!(syn::parse_file(&code).is_ok());
+
+    let metadata = Some(Metadata::new(Some("Items".to_owned()), None, None, None));
+    let merged = merge_psp37(metadata, true).unwrap();
+    let code = prettifier::unparse(merged.main_file());
+    assert!(code.contains("use psp37_full::{Id, PSP37, PSP37Data, PSP37Error, PSP37Event};"));
+    assert!(!code.contains("mod data;"));
+    let report = merged.report.extensions.iter().find(|report| report.extension == "metadata").unwrap();
+    assert_eq!(report.warnings, vec!["PSP37 has no collection metadata, supplied metadata is ignored"]);
+    assert_eq!("PSP37".parse::<Standard>(), Ok(Standard::PSP37));
+
+    let without_init = extension(ExtensionKind::Metadata, &fragments[4].1.replace("#[smart_beaver::init(Default::default())]", ""));
+    let error = merge_with(&files(syn::parse_str(PSP37_BASE_CONTRACT).unwrap()), vec![without_init], MergeOptions::standard(Standard::PSP37)).err().unwrap();
+    assert_eq!(error.to_string(), "Merge failed: Metadata field attributes has to be initialised with #[smart_beaver::init(..)]");
+}
+
+#[test]
+fn standards_can_be_declared_in_registry_manifest() {
+    let registry = Registry::parse(r#"{
+        "standards": [{
+            "name": "PSP22Lite",
+            "rules": "PSP22",
+            "external_crate": { "name": "psp22-lite", "version": "0.1.0" },
+            "extensions": [
+                { "name": "ownable", "prefix": "access" },
+                { "name": "mintable", "dependencies": ["ownable"] }
+            ],
+            "files": ["lib.rs", "Cargo.toml"],
+            "contract_mod_only": true
+        }]
+    }"#).unwrap();
+    let standard = registry.standard("PSP22Lite").unwrap();
+    assert_eq!(standard.extension_path(&ExtensionKind::Ownable), "access/ownable");
+    assert_eq!(standard.files, vec![OutputFile::Main, OutputFile::Cargo]);
+
+    let resolved = resolve_extensions(standard, &[ExtensionKind::Mintable]).unwrap();
+    assert_eq!(resolved.iter().map(|extension| extension.kind.clone()).collect::<Vec<_>>(), vec![ExtensionKind::Mintable, ExtensionKind::Ownable]);
+    let error = resolve_extensions(standard, &[ExtensionKind::Burnable]).err().unwrap();
+    assert_eq!(error.to_string(), "Invalid extension selection: burnable extension is not available for PSP22Lite");
+
+    let base_contract = BASE_CONTRACT.replace("mod token {", "mod token {\n    use crate::PSP22;");
+    let extensions = vec![extension(ExtensionKind::Ownable, OWNABLE), extension(ExtensionKind::Mintable, MINTABLE)];
+    let merged = merge_with(&files(syn::parse_str(&base_contract).unwrap()), extensions, MergeOptions { standard: standard.clone(), single_file_mode: true, ..MergeOptions::default() }).unwrap();
+    assert!(prettifier::unparse(merged.main_file()).contains("use psp22_lite::PSP22;"));
+
+    //Omitted fields are taken from the built-in standard of the same rules
+    let registry = Registry::parse("[[standards]]\nname = \"PSP34Lite\"\nrules = \"PSP34\"\nextensions = [{ name = \"burnable\" }, { name = \"ownable\" }]").unwrap();
+    let standard = registry.standard("PSP34Lite").unwrap();
+    let psp34 = StandardDescriptor::builtin(Standard::PSP34);
+    assert_eq!(standard.extension_path(&ExtensionKind::Ownable), "security/ownable");
+    assert_eq!(standard.dependencies(&ExtensionKind::Burnable), psp34.dependencies(&ExtensionKind::Burnable));
+    assert_eq!(standard.files, psp34.files);
+    assert_eq!(standard.contract_mod_only, psp34.contract_mod_only);
+
+    assert_eq!(Registry::parse("[[standards]]\nname = \"PSP22\"\nextensions = [{ name = \"staking\" }]").err().unwrap().to_string(), "Invalid standards registry: Unknown extension: staking of PSP22");
+}
+
+#[test]
+fn extension_descriptor_is_built_from_fragment() {
+    let fragment: syn::File = syn::parse_str(r#"
+#[smart_beaver::extension(title = "Ownable", description = "Single owner", conflicts = [wrapper], standards = [PSP22, PSP34])]
+mod ownable {
+    #[smart_beaver::storage(except = [new_default])]
+    pub struct Token {
+        #[smart_beaver::param(name = "initial_owner", priority = 5, required = false, default = Self::env().caller())]
+        owner: AccountId,
+        #[smart_beaver::init(None)]
+        pending_owner: Option<AccountId>,
+    }
+}
+"#).unwrap();
+    let psp22 = StandardDescriptor::builtin(Standard::PSP22);
+
+    let descriptor = ExtensionDescriptor::from_fragment(&ExtensionKind::Ownable, &fragment, &psp22).unwrap();
+    assert_eq!(descriptor.name, "security/ownable");
+    assert_eq!(descriptor.title, "Ownable");
+    assert_eq!(descriptor.description.as_deref(), Some("Single owner"));
+    assert_eq!(descriptor.conflicts, vec!["security/access_control", "wrapper"]);
+    assert_eq!(descriptor.files, vec!["lib.rs"]);
+    assert_eq!(descriptor.constructor_params.iter().map(|param| (param.name.as_str(), param.required, param.priority)).collect::<Vec<_>>(), vec![("initial_owner", false, Some(5))]);
+    assert_eq!(descriptor.excluded_constructors, vec!["new_default"]);
+
+    assert!(descriptor.validate(&psp22, &[ExtensionKind::Ownable]).is_ok());
+    assert_eq!(descriptor.validate(&psp22, &[ExtensionKind::Ownable, ExtensionKind::Wrapper]).err().unwrap().to_string(), "Invalid extension selection: security/ownable can not be used together with wrapper");
+    assert_eq!(descriptor.validate(&StandardDescriptor::builtin(Standard::PSP37), &[ExtensionKind::Ownable]).err().unwrap().to_string(), "Invalid extension selection: security/ownable extension supports only PSP22, PSP34");
+
+    //Only the published fragment is described, title defaults to the extension name
+    let loader = MemoryLoader::new(HashMap::from([("PSP22/extensions/security/ownable.trs".to_owned(), OWNABLE.to_owned())]));
+    let descriptors = block_on(describe_extensions(&loader, "PSP22")).unwrap();
+    assert_eq!(descriptors.iter().map(|descriptor| (descriptor.name.as_str(), descriptor.title.as_str())).collect::<Vec<_>>(), vec![("security/ownable", "ownable")]);
+}
+
+const VESTING: &str = r#"
+#[smart_beaver::extension]
+mod vesting {
+    #[smart_beaver::storage]
+    pub struct Token {
+        vesting_end: Timestamp,
+    }
+
+    impl Token {
+        #[ink(message)]
+        pub fn vesting_end(&self) -> Timestamp {
+            self.vesting_end
+        }
+    }
+}
+"#;
+
+#[test]
+fn custom_extensions_are_merged_like_builtin_ones() {
+    let vesting: ExtensionKind = "custom:finance/vesting".parse().unwrap();
+    assert_eq!(vesting, ExtensionKind::Custom("finance/vesting".to_owned()));
+    assert!("finance/vesting".parse::<ExtensionKind>().is_err());
+    assert!("custom:Vesting!".parse::<ExtensionKind>().is_err());
+
+    let psp22 = StandardDescriptor::builtin(Standard::PSP22);
+    let resolved = resolve_extensions(&psp22, &[vesting.clone(), ExtensionKind::Mintable]).unwrap();
+    assert_eq!(resolved.iter().map(|extension| extension.kind.path()).collect::<Vec<_>>(), vec!["finance/vesting", "mintable", "security/ownable"]);
+
+    let extensions = vec![extension(vesting, VESTING), extension(ExtensionKind::Ownable, OWNABLE), extension(ExtensionKind::Mintable, MINTABLE)];
+    let merged = merge_with(&files(base_contract()), extensions, MergeOptions::default()).unwrap();
+    let code = prettifier::unparse(merged.main_file());
+    assert!(code.contains("pub fn new(supply: u128, owner: AccountId, vesting_end: Timestamp) -> Self"));
+    assert!(code.contains("pub fn vesting_end(&self) -> Timestamp"));
+    assert_eq!(merged.report.extensions.last().unwrap().extension, "finance/vesting");
+}
+
+#[test]
+fn dependencies_declared_by_custom_fragments_are_resolved() {
+    let sources = HashMap::from([
+        ("PSP22/lib.rs".to_owned(), BASE_CONTRACT.to_owned()),
+        ("PSP22/extensions/finance/vesting.trs".to_owned(), VESTING.replace("#[smart_beaver::extension]", "#[smart_beaver::extension(dependencies = [mintable])]")),
+        ("PSP22/extensions/mintable.trs".to_owned(), MINTABLE.to_owned()),
+        ("PSP22/extensions/security/ownable.trs".to_owned(), OWNABLE.to_owned()),
+    ]);
+    let request = GenerationRequest { extensions: vec!["custom:finance/vesting".to_owned()], ..inline_request(sources) };
+
+    let project = block_on(generate(request)).unwrap();
+    let vesting = ExtensionKind::Custom("finance/vesting".to_owned());
+    assert_eq!(project.extensions.iter().map(|extension| (extension.kind.clone(), extension.reason.clone())).collect::<Vec<_>>(), vec![
+        (vesting.clone(), InclusionReason::Requested),
+        (ExtensionKind::Mintable, InclusionReason::RequiredBy(vesting)),
+        (ExtensionKind::Ownable, InclusionReason::RequiredBy(ExtensionKind::Mintable)),
+    ]);
+    assert!(project.file("lib.rs").unwrap().contains("pub fn mint(&mut self, value: u128)"));
+}
+
+fn inline_request(sources: HashMap<String, String>) -> GenerationRequest {
+    GenerationRequest {
+        files: vec![OutputFile::Main],
+        sources,
+        ..GenerationRequest::new("PSP22", vec!["mintable".to_owned()], "http://localhost:1")
+    }
+}
+
+#[test]
+fn contract_is_generated_from_inline_sources() {
+    let mut sources = HashMap::from([
+        ("PSP22/lib.rs".to_owned(), BASE_CONTRACT.to_owned()),
+        ("PSP22/extensions/mintable.trs".to_owned(), MINTABLE.to_owned()),
+        ("PSP22/extensions/security/ownable.trs".to_owned(), OWNABLE.to_owned()),
+    ]);
+
+    let project = block_on(generate(inline_request(sources.clone()))).unwrap();
+    let code = project.file("lib.rs").unwrap();
+    assert!(code.contains("pub fn mint(&mut self, value: u128)"));
+    assert!(code.contains("pub fn new(supply: u128, owner: AccountId) -> Self"));
+    assert_eq!(project.constructors[0].params.len(), 2);
+
+    sources.remove("PSP22/extensions/mintable.trs");
+    match block_on(generate(inline_request(sources))).err().unwrap() {
+        GenerateError::Merge { error, conflicts } => {
+            assert_eq!(error.to_string(), "Source file not found: PSP22/extensions/mintable.trs");
+            assert!(conflicts.is_empty());
+        }
+        error => panic!("Unexpected error: {}", error),
+    }
+}
+
+#[test]
+fn missing_static_files_are_reported_with_merged_contract() {
+    let sources = HashMap::from([
+        ("PSP22/lib.rs".to_owned(), BASE_CONTRACT.to_owned()),
+        ("PSP22/extensions/mintable.trs".to_owned(), MINTABLE.to_owned()),
+        ("PSP22/extensions/security/ownable.trs".to_owned(), OWNABLE.to_owned()),
+    ]);
+    let request = GenerationRequest { files: vec![OutputFile::Main, OutputFile::Cargo], ..inline_request(sources) };
+
+    match block_on(generate(request)).err().unwrap() {
+        GenerateError::StaticContent { error, project } => {
+            assert_eq!(error.to_string(), "Failed to download file: Static content Cargo.toml could not be downloaded.");
+            assert!(project.files.is_empty());
+            assert_eq!(project.extensions.len(), 2);
+        }
+        error => panic!("Unexpected error: {}", error),
+    }
+}
+
+#[test]
+fn contract_is_generated_from_local_templates() {
+    let root = std::env::temp_dir().join(format!("ink_generator_templates_{}", std::process::id()));
+    std::fs::create_dir_all(root.join("PSP22/extensions/security")).unwrap();
+    std::fs::write(root.join("PSP22/lib.rs"), BASE_CONTRACT).unwrap();
+    std::fs::write(root.join("PSP22/extensions/mintable.trs"), MINTABLE).unwrap();
+    std::fs::write(root.join("PSP22/extensions/security/ownable.trs"), OWNABLE).unwrap();
+
+    let loader = FsLoader::new(&root);
+    assert_eq!(block_on(loader.load("PSP22/lib.rs")).unwrap(), BASE_CONTRACT);
+    let output = block_on(run(&inline_request(HashMap::new()), &loader)).unwrap();
+    assert!(output.files[&OutputFile::Main].contains("pub fn mint(&mut self, value: u128)"));
+
+    //Natively the source is a directory with templates
+    let request = GenerationRequest { files: vec![OutputFile::Main], ..GenerationRequest::new("PSP22", vec!["mintable".to_owned()], root.to_str().unwrap()) };
+    let project = block_on(generate(request)).unwrap();
+    assert_eq!(project.file("lib.rs").unwrap(), output.files[&OutputFile::Main]);
+
+    std::fs::remove_dir_all(root).unwrap();
+}